@@ -1,24 +1,30 @@
 use aes::{
+    cipher::generic_array::GenericArray,
     cipher::{consts::U16, KeyInit},
-    cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt},
     Aes128,
 };
 use std::collections::HashSet;
 
-use crate::pad;
-use crate::xor::xor_bytes;
+use crate::mode::{self, Cbc, Ecb, Mode};
+use crate::pad::PaddingError;
+
+pub use crate::mode::CounterLayout;
 
 pub const KEY_SIZE: usize = 16;
 
 type Key = GenericArray<u8, U16>;
 pub type Block = GenericArray<u8, U16>;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CipherMode {
     ECB,
     CBC,
+    CTR,
 }
 
+/// AES-128 in CTR mode.
+pub type Ctr = mode::Ctr<Aes128>;
+
 fn make_key(bytes: &[u8]) -> Key {
     GenericArray::clone_from_slice(bytes)
 }
@@ -39,58 +45,45 @@ pub fn into_blocks(bytes: &[u8]) -> Vec<Block> {
 }
 
 pub fn encrypt_ecb(bytes: &[u8], key: &[u8]) -> Vec<u8> {
-    let cipher = make_cipher(key);
-    let bytes = pad::pad_block(bytes, KEY_SIZE);
-    let mut blocks = into_blocks(&bytes);
-    cipher.encrypt_blocks(blocks.as_mut_slice());
-    blocks.iter().cloned().flatten().collect()
+    Ecb::new(make_cipher(key)).encrypt(bytes)
 }
 
-pub fn decrypt_ecb(bytes: &[u8], key: &[u8]) -> Vec<u8> {
-    let cipher = make_cipher(key);
-    let mut blocks = into_blocks(bytes);
-    cipher.decrypt_blocks(blocks.as_mut_slice());
-    let blocks: Vec<u8> = blocks.iter().cloned().flatten().collect();
-    pad::remove_padding(&blocks)
+pub fn decrypt_ecb(bytes: &[u8], key: &[u8]) -> Result<Vec<u8>, PaddingError> {
+    Ecb::new(make_cipher(key)).decrypt(bytes)
 }
 
 pub fn encrypt_cbc(bytes: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
-    let cipher = make_cipher(key);
-    let bytes = pad::pad_block(bytes, KEY_SIZE);
-    let mut prev = iv.to_vec();
-    let mut enc = Vec::new();
-    for block in bytes.chunks(key.len()) {
-        let block = xor_bytes(block, &prev);
-        let mut block = make_block(&block);
-        cipher.encrypt_block(&mut block);
-        let mut block: Vec<u8> = block.iter().cloned().collect();
-        prev = block.clone();
-        enc.append(&mut block);
-    }
-    enc
+    Cbc::new(make_cipher(key), iv).encrypt(bytes)
 }
 
-pub fn decrypt_cbc(bytes: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
-    let cipher = make_cipher(key);
-    let mut prev = iv.to_vec();
-    let mut dec = Vec::new();
-    for block in bytes.chunks(KEY_SIZE) {
-        let ct = block.to_vec();
-        // decrypt
-        let mut block = make_block(block);
-        cipher.decrypt_block(&mut block);
-        // xor with prev
-        let block: Vec<u8> = block.iter().cloned().collect();
-        let mut block = xor_bytes(&block, &prev);
-        // prev <- ct clone
-        dec.append(&mut block);
-        prev = ct;
-    }
-    dec
+pub fn decrypt_cbc(bytes: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, PaddingError> {
+    Cbc::new(make_cipher(key), iv).decrypt(bytes)
+}
+
+/// Encrypt bytes with AES-128 in CTR mode, using the Cryptopals nonce/counter layout.
+pub fn encrypt_ctr(bytes: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    Ctr::new(make_cipher(key), nonce, CounterLayout::NonceLe64).encrypt(bytes)
+}
+
+/// CTR is symmetric, decrypting is the same operation as encrypting.
+pub fn decrypt_ctr(bytes: &[u8], key: &[u8], nonce: &[u8]) -> Vec<u8> {
+    encrypt_ctr(bytes, key, nonce)
+}
+
+/// Replace the plaintext under a CTR ciphertext from `offset` on with `newtext`, without
+/// touching the rest of it.
+pub fn edit_ctr(bytes: &[u8], key: &[u8], nonce: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    Ctr::new(make_cipher(key), nonce, CounterLayout::NonceLe64).edit(
+        &mut bytes,
+        offset as u64,
+        newtext,
+    );
+    bytes
 }
 
 pub fn detect_ecb(bytes: &[u8]) -> bool {
-    if bytes.len() % KEY_SIZE != 0 {
+    if !bytes.len().is_multiple_of(KEY_SIZE) {
         panic!("detect_ecb: bytes len not multiple of block_size");
     }
     let mut seen = HashSet::new();
@@ -106,18 +99,111 @@ pub fn detect_ecb(bytes: &[u8]) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::{decrypt_ecb, encrypt_ecb};
+    use super::{
+        decrypt_cbc, decrypt_ctr, decrypt_ecb, edit_ctr, encrypt_cbc, encrypt_ctr, encrypt_ecb,
+        make_cipher, CounterLayout, Ctr,
+    };
+    use crate::b64;
+    use crate::hex;
+    use crate::pad::PaddingError;
+
     #[test]
     fn test_encrypt_decrypt_ecb() {
         let key = b"YELLOW SUBMARINE";
         let pt = b"OSTENSIBLY, YES";
         let enc = encrypt_ecb(pt, key);
-        let dec = decrypt_ecb(&enc, key);
+        let dec = decrypt_ecb(&enc, key).unwrap();
         assert_eq!(dec, pt);
 
         let pt = b"YELLOW SUBMARINE";
         let enc = encrypt_ecb(pt, key);
-        let dec = decrypt_ecb(&enc, key);
+        let dec = decrypt_ecb(&enc, key).unwrap();
         assert_eq!(dec, pt);
     }
+
+    #[test]
+    fn test_decrypt_cbc_padding() {
+        let key = b"YELLOW SUBMARINE";
+        let iv = [0u8; 16];
+        let pt = b"I'm back and I'm ringin' the bell";
+        let mut enc = encrypt_cbc(pt, key, &iv);
+        assert_eq!(decrypt_cbc(&enc, key, &iv).unwrap(), pt);
+
+        // garbling the last block makes the padding invalid
+        let n = enc.len();
+        enc[n - 1] ^= 0xff;
+        assert_eq!(
+            decrypt_cbc(&enc, key, &iv),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            decrypt_cbc(&enc[1..], key, &iv),
+            Err(PaddingError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_decrypt_ctr() {
+        // Cryptopals challenge 18
+        let ct =
+            b64::decode("L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==")
+                .unwrap();
+        let pt = decrypt_ctr(&ct, b"YELLOW SUBMARINE", &[0u8; 8]);
+        assert_eq!(
+            &pt[..],
+            &b"Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby "[..]
+        );
+        assert_eq!(encrypt_ctr(&pt, b"YELLOW SUBMARINE", &[0u8; 8]), ct);
+    }
+
+    #[test]
+    fn test_ctr_be128() {
+        // NIST SP 800-38A F.5.1, first two blocks
+        let key = hex::parse("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+        let iv = hex::parse("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap();
+        let mut bytes =
+            hex::parse("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51").unwrap();
+        Ctr::new(make_cipher(&key), &iv, CounterLayout::Be128).apply_keystream(&mut bytes);
+        assert_eq!(
+            hex::encode(&bytes),
+            "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff"
+        );
+    }
+
+    #[test]
+    fn test_ctr_seek() {
+        let key = b"YELLOW SUBMARINE";
+        let pt = b"the quick brown fox jumps over the lazy dog".to_vec();
+        let ct = encrypt_ctr(&pt, key, &[7u8; 8]);
+
+        let mut ctr = Ctr::new(make_cipher(key), &[7u8; 8], CounterLayout::NonceLe64);
+        ctr.seek(13);
+        let mut tail = ct[13..].to_vec();
+        ctr.apply_keystream(&mut tail);
+        assert_eq!(tail, &pt[13..]);
+        assert_eq!(ctr.position(), pt.len() as u64);
+    }
+
+    #[test]
+    fn test_edit_ctr() {
+        let key = b"YELLOW SUBMARINE";
+        let nonce = [3u8; 8];
+        let ct = encrypt_ctr(b"the quick brown fox", key, &nonce);
+
+        let edited = edit_ctr(&ct, key, &nonce, 4, b"slow ");
+        assert_eq!(&edited[..4], &ct[..4]);
+        assert_eq!(&edited[9..], &ct[9..]);
+        assert_eq!(decrypt_ctr(&edited, key, &nonce), b"the slow  brown fox");
+
+        let edited = edit_ctr(&ct, key, &nonce, 16, b"xes jump");
+        assert_eq!(
+            decrypt_ctr(&edited, key, &nonce),
+            b"the quick brown xes jump"
+        );
+        let edited = edit_ctr(&ct, key, &nonce, 21, b"!");
+        assert_eq!(
+            decrypt_ctr(&edited, key, &nonce),
+            b"the quick brown fox\0\0!"
+        );
+    }
 }
@@ -0,0 +1,679 @@
+//! Arbitrary precision unsigned integers, for the public key challenges.
+//! Numbers are stored as little-endian u32 limbs with no leading (most significant) zeros.
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Rem, Shl, Shr, Sub};
+use std::str::FromStr;
+
+use crate::hex;
+use crate::rand::Rng;
+
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { limbs: vec![] }
+    }
+
+    pub fn one() -> Self {
+        BigUint::from(1u64)
+    }
+
+    fn from_limbs(limbs: Vec<u32>) -> Self {
+        let mut n = BigUint { limbs };
+        n.normalize();
+        n
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.limbs == [1]
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|l| l & 1 == 0)
+    }
+
+    pub fn is_odd(&self) -> bool {
+        !self.is_even()
+    }
+
+    /// The value as a u64, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs.len() {
+            0 => Some(0),
+            1 => Some(self.limbs[0] as u64),
+            2 => Some(self.limbs[0] as u64 | (self.limbs[1] as u64) << 32),
+            _ => None,
+        }
+    }
+
+    /// Number of significant bits. Zero has none.
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        self.limbs
+            .get(i / 32)
+            .is_some_and(|l| (l >> (i % 32)) & 1 == 1)
+    }
+
+    pub fn set_bit(&mut self, i: usize) {
+        if self.limbs.len() <= i / 32 {
+            self.limbs.resize(i / 32 + 1, 0);
+        }
+        self.limbs[i / 32] |= 1 << (i % 32);
+    }
+
+    /// Number of zero bits below the lowest set bit. Zero has none.
+    pub fn trailing_zeros(&self) -> usize {
+        match self.limbs.iter().position(|&l| l != 0) {
+            Some(i) => i * 32 + self.limbs[i].trailing_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn from_bytes_be(bytes: &[u8]) -> Self {
+        let limbs = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0u32, |acc, &b| acc << 8 | b as u32))
+            .collect();
+        BigUint::from_limbs(limbs)
+    }
+
+    /// Big-endian bytes with no leading zeros. Zero is the empty string.
+    pub fn to_bytes_be(&self) -> Vec<u8> {
+        let bytes: Vec<u8> = self
+            .limbs
+            .iter()
+            .rev()
+            .flat_map(|l| l.to_be_bytes().to_vec())
+            .collect();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        bytes[skip..].to_vec()
+    }
+
+    /// Big-endian bytes, left-padded with zeros to `len` bytes.
+    pub fn to_bytes_be_padded(&self, len: usize) -> Vec<u8> {
+        let bytes = self.to_bytes_be();
+        assert!(bytes.len() <= len, "to_bytes_be_padded: number too large");
+        let mut padded = vec![0u8; len - bytes.len()];
+        padded.extend(bytes);
+        padded
+    }
+
+    /// Parse a hexadecimal string of any length.
+    pub fn from_hex(s: impl AsRef<str>) -> Result<Self, String> {
+        let mut s = s.as_ref().to_ascii_lowercase();
+        if s.len() % 2 != 0 {
+            s.insert(0, '0');
+        }
+        Ok(BigUint::from_bytes_be(&hex::parse(s)?))
+    }
+
+    /// Lowercase hexadecimal, without leading zeros.
+    pub fn to_hex(&self) -> String {
+        let s = hex::encode(&self.to_bytes_be());
+        match s.trim_start_matches('0') {
+            "" => "0".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    }
+
+    /// A random number of at most `bits` bits.
+    pub fn random_bits<R: Rng>(rng: &mut R, bits: usize) -> BigUint {
+        let mut bytes = rng.get_bytes(bits.div_ceil(8) as u64);
+        if !bits.is_multiple_of(8) {
+            bytes[0] &= (1 << (bits % 8)) - 1;
+        }
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    /// A uniformly random number in [0, bound).
+    pub fn random_below<R: Rng>(rng: &mut R, bound: &BigUint) -> BigUint {
+        assert!(!bound.is_zero(), "random_below: empty range");
+        loop {
+            let n = BigUint::random_bits(rng, bound.bits());
+            if &n < bound {
+                return n;
+            }
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigUint {
+        let mut base = self.clone();
+        let mut result = BigUint::one();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// self^exp mod modulus, by square and multiply.
+    pub fn modpow(&self, exp: &BigUint, modulus: &BigUint) -> BigUint {
+        assert!(!modulus.is_zero(), "modpow: zero modulus");
+        if modulus.is_one() {
+            return BigUint::zero();
+        }
+        let base = self % modulus;
+        let mut result = BigUint::one();
+        for i in (0..exp.bits()).rev() {
+            result = &(&result * &result) % modulus;
+            if exp.bit(i) {
+                result = &(&result * &base) % modulus;
+            }
+        }
+        result
+    }
+
+    pub fn gcd(&self, other: &BigUint) -> BigUint {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while !b.is_zero() {
+            let r = &a % &b;
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// The inverse of self modulo m, or None if they aren't coprime.
+    pub fn modinv(&self, m: &BigUint) -> Option<BigUint> {
+        // extended Euclid, keeping the coefficients reduced mod m so they stay positive
+        let (mut old_r, mut r) = (self % m, m.clone());
+        let (mut old_s, mut s) = (BigUint::one(), BigUint::zero());
+        while !r.is_zero() {
+            let (q, rem) = old_r.divrem(&r);
+            old_r = r;
+            r = rem;
+            let qs = &(&q * &s) % m;
+            let new_s = &(&old_s + m) - &qs;
+            old_s = s;
+            s = &new_s % m;
+        }
+        if old_r.is_one() {
+            Some(&old_s % m)
+        } else {
+            None
+        }
+    }
+
+    /// Floor of the n-th root, by Newton's method.
+    pub fn nth_root(&self, n: u32) -> BigUint {
+        assert!(n > 0, "nth_root: zeroth root");
+        if self.is_zero() || n == 1 {
+            return self.clone();
+        }
+        // start from a power of two above the root and walk down
+        let mut x = BigUint::one() << self.bits().div_ceil(n as usize);
+        let n_big = BigUint::from(n as u64);
+        let n_minus_1 = BigUint::from(n as u64 - 1);
+        loop {
+            let y = (&(&n_minus_1 * &x) + &(self / &x.pow(n - 1))) / &n_big;
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
+
+    pub fn cbrt(&self) -> BigUint {
+        self.nth_root(3)
+    }
+
+    /// Quotient and remainder (Knuth, TAOCP vol. 2, 4.3.1 algorithm D).
+    pub fn divrem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+        assert!(!divisor.is_zero(), "divrem: division by zero");
+        if self < divisor {
+            return (BigUint::zero(), self.clone());
+        }
+        if divisor.limbs.len() == 1 {
+            let (q, r) = self.divrem_u32(divisor.limbs[0]);
+            return (q, BigUint::from(r as u64));
+        }
+
+        // normalize so the divisor's top limb has its high bit set
+        let shift = divisor.limbs.last().unwrap().leading_zeros() as usize;
+        let v = (divisor << shift).limbs;
+        let mut u = (self << shift).limbs;
+        if u.len() == self.limbs.len() {
+            u.push(0);
+        }
+        let n = v.len();
+        let m = u.len() - n - 1;
+        let base = 1u64 << 32;
+        let mut q = vec![0u32; m + 1];
+
+        for j in (0..=m).rev() {
+            let num = (u[j + n] as u64) << 32 | u[j + n - 1] as u64;
+            let mut qhat = num / v[n - 1] as u64;
+            let mut rhat = num % v[n - 1] as u64;
+            while qhat >= base || qhat * v[n - 2] as u64 > (rhat << 32 | u[j + n - 2] as u64) {
+                qhat -= 1;
+                rhat += v[n - 1] as u64;
+                if rhat >= base {
+                    break;
+                }
+            }
+
+            // u[j..j + n + 1] -= qhat * v
+            let mut borrow = 0i64;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let p = qhat * v[i] as u64 + carry;
+                carry = p >> 32;
+                let t = u[i + j] as i64 - borrow - (p & 0xffffffff) as i64;
+                u[i + j] = t as u32;
+                borrow = (t < 0) as i64;
+            }
+            let t = u[j + n] as i64 - borrow - carry as i64;
+            u[j + n] = t as u32;
+
+            // qhat was one too many, add the divisor back
+            if t < 0 {
+                qhat -= 1;
+                let mut carry = 0u64;
+                for i in 0..n {
+                    let s = u[i + j] as u64 + v[i] as u64 + carry;
+                    u[i + j] = s as u32;
+                    carry = s >> 32;
+                }
+                u[j + n] = u[j + n].wrapping_add(carry as u32);
+            }
+            q[j] = qhat as u32;
+        }
+
+        u.truncate(n);
+        (BigUint::from_limbs(q), BigUint::from_limbs(u) >> shift)
+    }
+
+    fn divrem_u32(&self, divisor: u32) -> (BigUint, u32) {
+        let mut rem = 0u64;
+        let mut q = vec![0u32; self.limbs.len()];
+        for (i, &l) in self.limbs.iter().enumerate().rev() {
+            let cur = rem << 32 | l as u64;
+            q[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        (BigUint::from_limbs(q), rem as u32)
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        BigUint::from_limbs(vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Decimal representation.
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad("0");
+        }
+        // peel off nine decimal digits at a time
+        let mut chunks = vec![];
+        let mut n = self.clone();
+        while !n.is_zero() {
+            let (q, r) = n.divrem_u32(1_000_000_000);
+            chunks.push(r);
+            n = q;
+        }
+        let mut s = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&s)
+    }
+}
+
+impl fmt::Debug for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BigUint(0x{})", self.to_hex())
+    }
+}
+
+impl fmt::LowerHex for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_hex())
+    }
+}
+
+/// Parse a decimal string.
+impl FromStr for BigUint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("invalid decimal string".to_string());
+        }
+        let ten = BigUint::from(10u64);
+        s.chars().try_fold(BigUint::zero(), |acc, c| {
+            let digit = c
+                .to_digit(10)
+                .ok_or_else(|| "invalid decimal string".to_string())?;
+            Ok(&(&acc * &ten) + &BigUint::from(digit as u64))
+        })
+    }
+}
+
+fn add(a: &BigUint, b: &BigUint) -> BigUint {
+    let (long, short) = if a.limbs.len() >= b.limbs.len() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let mut limbs = Vec::with_capacity(long.limbs.len() + 1);
+    let mut carry = 0u64;
+    for (i, &l) in long.limbs.iter().enumerate() {
+        let s = l as u64 + *short.limbs.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push(s as u32);
+        carry = s >> 32;
+    }
+    limbs.push(carry as u32);
+    BigUint::from_limbs(limbs)
+}
+
+fn sub(a: &BigUint, b: &BigUint) -> BigUint {
+    assert!(a >= b, "BigUint subtraction underflow");
+    let mut limbs = Vec::with_capacity(a.limbs.len());
+    let mut borrow = 0i64;
+    for (i, &l) in a.limbs.iter().enumerate() {
+        let t = l as i64 - *b.limbs.get(i).unwrap_or(&0) as i64 - borrow;
+        limbs.push(t as u32);
+        borrow = (t < 0) as i64;
+    }
+    BigUint::from_limbs(limbs)
+}
+
+fn mul(a: &BigUint, b: &BigUint) -> BigUint {
+    if a.is_zero() || b.is_zero() {
+        return BigUint::zero();
+    }
+    let mut limbs = vec![0u32; a.limbs.len() + b.limbs.len()];
+    for (i, &x) in a.limbs.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.limbs.iter().enumerate() {
+            let t = x as u64 * y as u64 + limbs[i + j] as u64 + carry;
+            limbs[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        limbs[i + b.limbs.len()] = carry as u32;
+    }
+    BigUint::from_limbs(limbs)
+}
+
+fn div(a: &BigUint, b: &BigUint) -> BigUint {
+    a.divrem(b).0
+}
+
+fn rem(a: &BigUint, b: &BigUint) -> BigUint {
+    a.divrem(b).1
+}
+
+fn bitand(a: &BigUint, b: &BigUint) -> BigUint {
+    let limbs = a.limbs.iter().zip(b.limbs.iter()).map(|(x, y)| x & y);
+    BigUint::from_limbs(limbs.collect())
+}
+
+fn bitor(a: &BigUint, b: &BigUint) -> BigUint {
+    let len = a.limbs.len().max(b.limbs.len());
+    let limbs = (0..len).map(|i| a.limbs.get(i).unwrap_or(&0) | b.limbs.get(i).unwrap_or(&0));
+    BigUint::from_limbs(limbs.collect())
+}
+
+fn bitxor(a: &BigUint, b: &BigUint) -> BigUint {
+    let len = a.limbs.len().max(b.limbs.len());
+    let limbs = (0..len).map(|i| a.limbs.get(i).unwrap_or(&0) ^ b.limbs.get(i).unwrap_or(&0));
+    BigUint::from_limbs(limbs.collect())
+}
+
+fn shl(a: &BigUint, n: usize) -> BigUint {
+    if a.is_zero() {
+        return BigUint::zero();
+    }
+    let (words, bits) = (n / 32, n % 32);
+    let mut limbs = vec![0u32; words];
+    let mut carry = 0u32;
+    for &l in a.limbs.iter() {
+        if bits == 0 {
+            limbs.push(l);
+        } else {
+            limbs.push(l << bits | carry);
+            carry = l >> (32 - bits);
+        }
+    }
+    limbs.push(carry);
+    BigUint::from_limbs(limbs)
+}
+
+fn shr(a: &BigUint, n: usize) -> BigUint {
+    let (words, bits) = (n / 32, n % 32);
+    if words >= a.limbs.len() {
+        return BigUint::zero();
+    }
+    let src = &a.limbs[words..];
+    let limbs = (0..src.len()).map(|i| {
+        if bits == 0 {
+            src[i]
+        } else {
+            src[i] >> bits | src.get(i + 1).map_or(0, |h| h << (32 - bits))
+        }
+    });
+    BigUint::from_limbs(limbs.collect())
+}
+
+// Implement an operator for every combination of owned and borrowed operands, and for u64
+// on the right hand side.
+macro_rules! forward_binop {
+    ($trait:ident, $method:ident, $imp:ident) => {
+        impl<'a, 'b> $trait<&'b BigUint> for &'a BigUint {
+            type Output = BigUint;
+            fn $method(self, other: &BigUint) -> BigUint {
+                $imp(self, other)
+            }
+        }
+
+        impl<'a> $trait<&'a BigUint> for BigUint {
+            type Output = BigUint;
+            fn $method(self, other: &BigUint) -> BigUint {
+                $imp(&self, other)
+            }
+        }
+
+        impl<'a> $trait<BigUint> for &'a BigUint {
+            type Output = BigUint;
+            fn $method(self, other: BigUint) -> BigUint {
+                $imp(self, &other)
+            }
+        }
+
+        impl $trait<BigUint> for BigUint {
+            type Output = BigUint;
+            fn $method(self, other: BigUint) -> BigUint {
+                $imp(&self, &other)
+            }
+        }
+
+        impl<'a> $trait<u64> for &'a BigUint {
+            type Output = BigUint;
+            fn $method(self, other: u64) -> BigUint {
+                $imp(self, &BigUint::from(other))
+            }
+        }
+
+        impl $trait<u64> for BigUint {
+            type Output = BigUint;
+            fn $method(self, other: u64) -> BigUint {
+                $imp(&self, &BigUint::from(other))
+            }
+        }
+    };
+}
+
+forward_binop!(Add, add, add);
+forward_binop!(Sub, sub, sub);
+forward_binop!(Mul, mul, mul);
+forward_binop!(Div, div, div);
+forward_binop!(Rem, rem, rem);
+forward_binop!(BitAnd, bitand, bitand);
+forward_binop!(BitOr, bitor, bitor);
+forward_binop!(BitXor, bitxor, bitxor);
+
+macro_rules! forward_shift {
+    ($trait:ident, $method:ident, $imp:ident) => {
+        impl<'a> $trait<usize> for &'a BigUint {
+            type Output = BigUint;
+            fn $method(self, n: usize) -> BigUint {
+                $imp(self, n)
+            }
+        }
+
+        impl $trait<usize> for BigUint {
+            type Output = BigUint;
+            fn $method(self, n: usize) -> BigUint {
+                $imp(&self, n)
+            }
+        }
+    };
+}
+
+forward_shift!(Shl, shl, shl);
+forward_shift!(Shr, shr, shr);
+
+#[cfg(test)]
+mod tests {
+    use super::BigUint;
+    use crate::rand::{Rng, SeedableRng, Xoshiro256};
+
+    fn big(n: u128) -> BigUint {
+        BigUint::from_bytes_be(&n.to_be_bytes())
+    }
+
+    #[test]
+    fn test_arithmetic_against_u128() {
+        let mut prng = Xoshiro256::seed_from_u64(1234);
+        for _ in 0..500 {
+            let a = prng.next_u64() as u128 * prng.range(1 << 20) as u128;
+            let b = prng.next_u64() as u128 >> prng.range(64);
+            assert_eq!(&big(a) + &big(b), big(a + b));
+            assert_eq!(&big(a) - &big(a.min(b)), big(a - a.min(b)));
+            assert_eq!(&big(a >> 40) * &big(b >> 20), big((a >> 40) * (b >> 20)));
+            if b != 0 {
+                let (q, r) = big(a).divrem(&big(b));
+                assert_eq!((q, r), (big(a / b), big(a % b)));
+            }
+            assert_eq!(&big(a) ^ &big(b), big(a ^ b));
+            assert_eq!(&big(a) & &big(b), big(a & b));
+            assert_eq!(&big(a) | &big(b), big(a | b));
+            assert_eq!(&big(a) >> 37, big(a >> 37));
+            assert_eq!(&big(a >> 40) << 27, big((a >> 40) << 27));
+            assert_eq!(big(a).cmp(&big(b)), a.cmp(&b));
+        }
+    }
+
+    #[test]
+    fn test_divrem_large() {
+        let mut prng = Xoshiro256::seed_from_u64(4321);
+        for _ in 0..50 {
+            let a = BigUint::from_bytes_be(&prng.get_bytes(128));
+            let len = 1 + prng.range(100);
+            let b = BigUint::from_bytes_be(&prng.get_bytes(len));
+            let (q, r) = a.divrem(&b);
+            assert!(r < b);
+            assert_eq!(&(&q * &b) + &r, a);
+        }
+    }
+
+    #[test]
+    fn test_conversions() {
+        let n = BigUint::from_hex("1ffffffffffffffffffffffffffffffff").unwrap();
+        assert_eq!(n.to_hex(), "1ffffffffffffffffffffffffffffffff");
+        assert_eq!(n.bits(), 129);
+        assert_eq!(BigUint::zero().to_hex(), "0");
+        assert_eq!(BigUint::zero().to_bytes_be(), Vec::<u8>::new());
+        assert_eq!(
+            BigUint::from(258u64).to_bytes_be_padded(4),
+            vec![0, 0, 1, 2]
+        );
+
+        let two128 = BigUint::one() << 128;
+        assert_eq!(
+            two128.to_string(),
+            "340282366920938463463374607431768211456"
+        );
+        assert_eq!(
+            "340282366920938463463374607431768211456".parse::<BigUint>(),
+            Ok(two128)
+        );
+        assert!("12a".parse::<BigUint>().is_err());
+        assert_eq!(format!("{:x}", BigUint::from(255u64)), "ff");
+    }
+
+    #[test]
+    fn test_number_theory() {
+        // textbook RSA example
+        let (e, phi) = (BigUint::from(17u64), BigUint::from(3120u64));
+        let d = e.modinv(&phi).unwrap();
+        assert_eq!(d, BigUint::from(2753u64));
+        assert_eq!(BigUint::from(6u64).modinv(&BigUint::from(9u64)), None);
+
+        let n = BigUint::from(3233u64);
+        let c = BigUint::from(65u64).modpow(&e, &n);
+        assert_eq!(c, BigUint::from(2790u64));
+        assert_eq!(c.modpow(&d, &n), BigUint::from(65u64));
+
+        // Fermat's little theorem with the Mersenne prime 2^127 - 1
+        let p = (BigUint::one() << 127) - 1;
+        let a = BigUint::from(0xdeadbeefu64);
+        assert!(a.modpow(&(&p - 1), &p).is_one());
+
+        assert_eq!(
+            BigUint::from(462u64).gcd(&BigUint::from(1071u64)),
+            BigUint::from(21u64)
+        );
+
+        let x = BigUint::from_hex("123456789abcdef0123456789abcdef").unwrap();
+        let cube = x.pow(3);
+        assert_eq!(cube.cbrt(), x);
+        assert_eq!((&cube + 1).cbrt(), x);
+        assert_eq!((&cube - 1).cbrt(), &x - 1);
+        assert_eq!(x.pow(5).nth_root(5), x);
+    }
+}
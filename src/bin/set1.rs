@@ -2,10 +2,12 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-use cryptopals::{b64, break_single_byte_xor, build_repeated_key, find_xor_key_size, hamming, hex};
+use cryptopals::repeating_xor::break_repeating_xor;
+use cryptopals::score::NgramModel;
+use cryptopals::{b64, find_xor_key_size, hamming, hex, rank_single_byte_xor, Candidate};
 
 use cryptopals::aes;
-use cryptopals::xor::xor_bytes;
+use cryptopals::xor::{xor_bytes, xor_repeating};
 
 fn challenge1() {
     let bytes = hex::parse(
@@ -29,30 +31,35 @@ fn challenge2() {
 fn challenge3() {
     let bytes =
         hex::parse("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736").unwrap();
-    if let Ok((_, _, broken)) = break_single_byte_xor(&bytes) {
-        assert_eq!("Cooking MC's like a pound of bacon", broken);
-    }
+    let ranked = rank_single_byte_xor(&bytes, &NgramModel::english_unigrams(), 3);
+    assert_eq!(
+        b"Cooking MC's like a pound of bacon",
+        &ranked[0].plaintext[..]
+    );
 }
 
 fn challenge4() {
     let txt = File::open("4.txt").unwrap();
-    let mut scores: Vec<_> = BufReader::new(txt)
+    let scorer = NgramModel::english_unigrams();
+    let mut candidates: Vec<_> = BufReader::new(txt)
         .lines()
-        .flatten()
+        .map_while(Result::ok)
         .flat_map(|line| {
             let bytes = hex::parse(line).unwrap();
-            break_single_byte_xor(&bytes)
+            rank_single_byte_xor(&bytes, &scorer, 1)
         })
         .collect();
-    scores.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-    assert_eq!("Now that the party is jumping\n", scores[0].2)
+    candidates.sort_by(Candidate::rank);
+    assert_eq!(
+        b"Now that the party is jumping\n",
+        &candidates[0].plaintext[..]
+    )
 }
 
 fn challenge5() {
     let bytes =
         "Burning 'em, if you ain't quick and nimble\nI go crazy when I hear a cymbal".as_bytes();
-    let key = build_repeated_key(b"ICE", bytes.len());
-    let encrypted = xor_bytes(bytes, &key);
+    let encrypted: Vec<u8> = xor_repeating(bytes, b"ICE").collect();
     assert_eq!(
         hex::encode(&encrypted),
         "0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272\
@@ -60,24 +67,6 @@ fn challenge5() {
     );
 }
 
-fn find_repxor_key(bytes: &[u8], keysize: usize) -> Result<Vec<u8>, String> {
-    let blocks = bytes.chunks(keysize).collect::<Vec<_>>();
-    let mut keybytes = vec![];
-    for i in 0..keysize {
-        let mut transposed = vec![];
-        for b in &blocks {
-            if b.len() <= i {
-                break;
-            }
-            transposed.push(b[i]);
-        }
-        let (_, key, _) = break_single_byte_xor(&transposed)?;
-        keybytes.push(key);
-    }
-
-    Ok(keybytes)
-}
-
 fn read_concat_lines(filename: &str) -> String {
     BufReader::new(File::open(filename).unwrap())
         .lines()
@@ -96,17 +85,19 @@ fn challenge6() {
 
     let b64txt = read_concat_lines("6.txt");
     let bytes = b64::decode(&b64txt).unwrap();
-    let keysize = find_xor_key_size(&bytes);
+    assert_eq!(find_xor_key_size(&bytes), Some(29));
 
-    let key = build_repeated_key(&find_repxor_key(&bytes, keysize).unwrap(), bytes.len());
-    let pt = String::from_utf8(xor_bytes(&bytes, &key)).unwrap();
-    assert!(pt.starts_with("I'm back and I'm ringin' the bell"));
+    let solutions = break_repeating_xor(&bytes, &NgramModel::english_unigrams(), 3);
+    assert_eq!(solutions[0].key, b"Terminator X: Bring the noise");
+    assert!(solutions[0]
+        .plaintext
+        .starts_with(b"I'm back and I'm ringin' the bell"));
 }
 
 fn challenge7() {
     let key = b"YELLOW SUBMARINE";
-    let bytes = b64::decode(&read_concat_lines("7.txt")).unwrap();
-    let bytes = aes::decrypt_ecb(&bytes, key);
+    let bytes = b64::decode(read_concat_lines("7.txt")).unwrap();
+    let bytes = aes::decrypt_ecb(&bytes, key).unwrap();
     let pt = String::from_utf8(bytes).unwrap();
     assert!(pt.starts_with("I'm back and I'm ringin' the bell"));
 }
@@ -114,7 +105,7 @@ fn challenge7() {
 fn challenge8() {
     let lines = BufReader::new(File::open("8.txt").unwrap())
         .lines()
-        .flatten()
+        .map_while(Result::ok)
         .flat_map(hex::parse);
     for (i, bytes) in lines.enumerate() {
         if aes::detect_ecb(&bytes) {
@@ -2,15 +2,17 @@
 extern crate log;
 
 use std::{
-    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader},
 };
 
+use cryptopals::aes;
 use cryptopals::b64;
+use cryptopals::bitflip;
+use cryptopals::byte_at_a_time::ByteAtATime;
+use cryptopals::cut_paste::{self, ProfileServer};
 use cryptopals::pad;
-use cryptopals::rand;
-use cryptopals::{aes, find_xor_key_size};
+use cryptopals::rand::{self, Clock, Rng, SystemClock};
 
 fn challenge9() {
     let b = b"YELLOW SUBMARINE";
@@ -30,15 +32,15 @@ fn read_concat_lines(filename: &str) -> String {
 }
 
 fn challenge10() {
-    let ct = b64::decode(&read_concat_lines("10.txt")).unwrap();
-    let dec = aes::decrypt_cbc(&ct, b"YELLOW SUBMARINE", &[0u8; 16]);
+    let ct = b64::decode(read_concat_lines("10.txt")).unwrap();
+    let dec = aes::decrypt_cbc(&ct, b"YELLOW SUBMARINE", &[0u8; 16]).unwrap();
     let s = String::from_utf8(dec).unwrap();
     assert!(s.starts_with("I'm back and I'm ringin' the bell"));
 }
 
-// Encrypt bytes under a random key
-fn encryption_oracle(bytes: &[u8]) -> (aes::CipherMode, Vec<u8>) {
-    let mut prng = rand::make_prng();
+// Encrypt bytes under a random key, from a generator seeded by the clock
+fn encryption_oracle<C: Clock>(bytes: &[u8], clock: &C) -> (aes::CipherMode, Vec<u8>) {
+    let mut prng = rand::make_prng_with(clock);
     // prepend and append 5 to 10 random bytes
     let (lo, hi) = (prng.range(5) + 5, prng.range(5) + 5);
     let bytes: Vec<u8> = prng
@@ -76,7 +78,7 @@ fn challenge11() {
     const RUNS: u32 = 100;
 
     for _ in 0..RUNS {
-        let (mode, bytes) = encryption_oracle(&plaintext);
+        let (mode, bytes) = encryption_oracle(&plaintext, &SystemClock);
         if mode == aes::CipherMode::CBC {
             total_cbc += 1;
         }
@@ -111,66 +113,65 @@ fn prepend_encrypt_ecb(plaintext: &[u8], unknown: &[u8], key: &[u8]) -> Vec<u8>
     aes::encrypt_ecb(&manipulated, key)
 }
 
-fn challenge12() {
-    let key = rand::bytes(aes::KEY_SIZE as u64);
-    let plaintext = Vec::from([b'A'; 128]);
-    let unknown = b64::decode(
+fn challenge12_unknown() -> Vec<u8> {
+    b64::decode(
         "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg\
         aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq\
         dXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUg\
         YnkK",
     )
-    .unwrap();
+    .unwrap()
+}
 
-    let enc = prepend_encrypt_ecb(&plaintext, &unknown, &key);
-    let guessed_keysize = find_xor_key_size(&enc);
-    debug!("challenge12: guessed keysize {}", guessed_keysize);
-    assert_eq!(guessed_keysize, aes::KEY_SIZE);
-
-    let guessed_mode = detection_oracle(&enc);
-    debug!("challenge12: guessed mode {:?}", guessed_mode);
-    assert_eq!(guessed_mode, aes::CipherMode::ECB);
-
-    let mut input = vec![b'A'; guessed_keysize - 1];
-    let mut dict = HashMap::new();
-    let mut decrypted: Vec<_> = Vec::new();
-
-    for unknown_chunk in unknown.chunks(guessed_keysize) {
-        let len = unknown_chunk.len();
-        for j in 0..len {
-            // create dict from ct to pt. Each block also has all bytes discovered so far and collected in input
-            for i in 0..=255u8 {
-                let mut block: Vec<u8> = input.clone();
-                block.push(i);
-                let mut enc = prepend_encrypt_ecb(&block, &unknown_chunk[j..], &key);
-                enc.truncate(guessed_keysize);
-                dict.insert(enc, block);
-            }
-
-            // encrypt unknown_chunk subsequent tails to guess one head byte at a time
-            let enc = &prepend_encrypt_ecb(&input, &unknown_chunk[j..], &key)[..guessed_keysize];
-
-            // get the plaintext
-            let plaintext = dict.remove(enc).unwrap();
-            let byte = plaintext[guessed_keysize - 1];
-
-            // no need to store last byte, there is nothing else to search
-            if j < len - 1 {
-                input[guessed_keysize - 1 - (j + 1)] = byte;
-            }
-
-            dict.clear();
-            decrypted.push(byte);
-
-            debug!(
-                "challenge12: byte {} of unknown found: {} / input {:?}",
-                j, byte, input
-            );
-        }
-    }
+fn challenge12() {
+    let key = rand::bytes(aes::KEY_SIZE as u64);
+    let unknown = challenge12_unknown();
+    let oracle = |input: &[u8]| prepend_encrypt_ecb(input, &unknown, &key);
+
+    let breaker = ByteAtATime::new(oracle).unwrap();
+    debug!("challenge12: guessed block size {}", breaker.block_size());
+    assert_eq!(breaker.block_size(), aes::KEY_SIZE);
+    assert_eq!(breaker.secret_len(), Ok(unknown.len()));
 
-    let plaintext = String::from_utf8_lossy(&decrypted);
-    info!("challenge12:\n{}", plaintext);
+    let decrypted = breaker.recover_secret().unwrap();
+    assert_eq!(decrypted, unknown);
+    info!("challenge12:\n{}", String::from_utf8_lossy(&decrypted));
+}
+
+fn challenge13() {
+    let server = ProfileServer::new();
+    let oracle = |email: &str| server.profile_for(email);
+    let cookie =
+        cut_paste::forge(oracle, aes::KEY_SIZE, "&uid=10&role=user", "role", "admin").unwrap();
+    assert_eq!(server.role(&cookie), Some("admin".to_string()));
+}
+
+fn challenge14() {
+    let key = rand::bytes(aes::KEY_SIZE as u64);
+    let unknown = challenge12_unknown();
+    let prefix = rand::bytes(rand::make_prng().range(64));
+    let oracle = |input: &[u8]| {
+        let bytes: Vec<u8> = prefix.iter().chain(input.iter()).cloned().collect();
+        prepend_encrypt_ecb(&bytes, &unknown, &key)
+    };
+
+    let breaker = ByteAtATime::new(oracle).unwrap();
+    assert_eq!(breaker.prefix_len(), Ok(prefix.len()));
+    assert_eq!(breaker.recover_secret().unwrap(), unknown);
+}
+
+fn challenge16() {
+    let server = bitflip::Server::new(aes::CipherMode::CBC).unwrap();
+    let flip = bitflip::inject(
+        |data: &[u8]| server.encrypt(data),
+        |ct: &[u8]| server.is_admin(ct),
+        aes::CipherMode::CBC,
+        aes::KEY_SIZE,
+        bitflip::Server::PREFIX.len(),
+        b";admin=true;",
+    )
+    .unwrap();
+    debug!("challenge16: scrambled blocks {:?}", flip.scrambled);
 }
 
 fn main() {
@@ -180,4 +181,7 @@ fn main() {
     challenge10();
     challenge11();
     challenge12();
+    challenge13();
+    challenge14();
+    challenge16();
 }
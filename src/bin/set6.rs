@@ -0,0 +1,56 @@
+/// The Cryptopals challenges, set 6: DSA key recovery.
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+use cryptopals::bigint::BigUint;
+use cryptopals::dsa::{self, Params, Signature};
+
+fn challenge43() {
+    let params = Params::cryptopals();
+    let y = BigUint::from_hex(
+        "84ad4719d044495496a3201c8ff484feb45b962e7302e56a392aee4abab3e4bd\
+         ebf2955b4736012f21a08084056b19bcd7fee56048e004e44984e2f411788efd\
+         c837a0d2e5abb7b555039fd243ac01f0fb2ed1dec568280ce678e931868d23eb\
+         095fde9d3779191b8c0299d6e07bbb283e6633451e535c45513b2d33c99ea17",
+    )
+    .unwrap();
+    let msg = b"For those that envy a MC it can be hazardous to your health\n\
+                So be friendly, a matter of life and death, just like a etch-a-sketch\n";
+    let sig = Signature {
+        r: "548099063082341131477253921760299949438196259240"
+            .parse()
+            .unwrap(),
+        s: "857042759984254168557880549501802188789837994940"
+            .parse()
+            .unwrap(),
+    };
+    let (_, x) = params
+        .brute_force_nonce(&y, &dsa::hash_message(msg), &sig, 0..1 << 16)
+        .unwrap();
+    assert_eq!(
+        dsa::fingerprint(&x),
+        "0954edd5e0afe5542a4adf012611a91912a3ec16"
+    );
+}
+
+fn challenge44() -> Result<(), String> {
+    let txt = File::open("44.txt").map_err(|e| format!("44.txt: {}", e))?;
+    let messages = dsa::parse_signed_messages(BufReader::new(txt))?;
+    let x = Params::cryptopals()
+        .find_repeated_nonce(&messages)
+        .ok_or_else(|| "no two messages share a nonce".to_string())?;
+    assert_eq!(
+        dsa::fingerprint(&x),
+        "ca8f6f7c66fa362d40760d135b763eb8527d3d52"
+    );
+    Ok(())
+}
+
+fn main() {
+    challenge43();
+    if let Err(e) = challenge44() {
+        eprintln!("challenge 44: {}", e);
+        process::exit(1);
+    }
+}
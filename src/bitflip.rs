@@ -0,0 +1,193 @@
+//! CBC and CTR bitflipping (Cryptopals challenges 16 and 26): edit a ciphertext so that part
+//! of our own, escaped input decrypts to a string the escaping was meant to keep out.
+use crate::aes::{self, CipherMode};
+use crate::kv::{Codec, Escaping};
+use crate::rand;
+
+/// What our input holds where the target string will end up. It must survive escaping.
+const PLACEHOLDER: u8 = b'A';
+
+/// Stand-in for a service that wraps user data in a cookie, escaping `;` and `=`, and
+/// later checks cookies for `admin=true`.
+pub struct Server {
+    key: Vec<u8>,
+    // IV for CBC, nonce for CTR
+    iv: Vec<u8>,
+    mode: CipherMode,
+    codec: Codec,
+}
+
+impl Server {
+    pub const PREFIX: &'static [u8] = b"comment1=cooking%20MCs;userdata=";
+    pub const SUFFIX: &'static [u8] = b";comment2=%20like%20a%20pound%20of%20bacon";
+
+    /// A server with a random key, encrypting with AES in CBC or CTR mode.
+    pub fn new(mode: CipherMode) -> Result<Self, String> {
+        let iv_len = match mode {
+            CipherMode::CBC => aes::KEY_SIZE,
+            CipherMode::CTR => aes::KEY_SIZE / 2,
+            CipherMode::ECB => return Err("bitflip: ECB has nothing to flip".to_string()),
+        };
+        Ok(Server {
+            key: rand::bytes(aes::KEY_SIZE as u64),
+            iv: rand::bytes(iv_len as u64),
+            mode,
+            codec: Codec::cookie(Escaping::Percent),
+        })
+    }
+
+    pub fn encrypt(&self, userdata: &[u8]) -> Vec<u8> {
+        let pt: Vec<u8> = Server::PREFIX
+            .iter()
+            .chain(self.codec.escape(userdata).iter())
+            .chain(Server::SUFFIX.iter())
+            .cloned()
+            .collect();
+        match self.mode {
+            CipherMode::CBC => aes::encrypt_cbc(&pt, &self.key, &self.iv),
+            _ => aes::encrypt_ctr(&pt, &self.key, &self.iv),
+        }
+    }
+
+    pub fn decrypt(&self, ct: &[u8]) -> Result<Vec<u8>, String> {
+        match self.mode {
+            CipherMode::CBC => aes::decrypt_cbc(ct, &self.key, &self.iv).map_err(|e| e.to_string()),
+            _ => Ok(aes::decrypt_ctr(ct, &self.key, &self.iv)),
+        }
+    }
+
+    /// Does the cookie decrypt to one with an `admin=true` field? Garbled bytes are fine as
+    /// long as they don't touch that field.
+    pub fn is_admin(&self, ct: &[u8]) -> bool {
+        self.decrypt(ct)
+            .map(|pt| pt.split(|&b| b == b';').any(|field| field == b"admin=true"))
+            .unwrap_or(false)
+    }
+}
+
+/// A ciphertext edited to carry the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flip {
+    pub ciphertext: Vec<u8>,
+    /// Indexes of the plaintext blocks the edit turned to garbage.
+    pub scrambled: Vec<usize>,
+}
+
+/// Make a ciphertext whose plaintext contains `target`, given an `encrypt` oracle that puts
+/// our input after `prefix_len` known bytes, and confirm it with `check`.
+///
+/// Our input is placeholder bytes where the target should go. Under CTR the ciphertext
+/// byte over each placeholder is flipped in place. Under CBC flipping a ciphertext byte
+/// flips the same byte of the next plaintext block but scrambles its own block, so the
+/// input starts with a whole sacrificial block and the target must fit in the block after
+/// it.
+pub fn inject<E, C>(
+    encrypt: E,
+    check: C,
+    mode: CipherMode,
+    block_size: usize,
+    prefix_len: usize,
+    target: &[u8],
+) -> Result<Flip, String>
+where
+    E: Fn(&[u8]) -> Vec<u8>,
+    C: Fn(&[u8]) -> bool,
+{
+    let (input, at, edit, scrambled) = match mode {
+        CipherMode::CTR => (
+            vec![PLACEHOLDER; target.len()],
+            prefix_len,
+            prefix_len,
+            vec![],
+        ),
+        CipherMode::CBC => {
+            if target.len() > block_size {
+                return Err("bitflip: target longer than a block".to_string());
+            }
+            let align = (block_size - prefix_len % block_size) % block_size;
+            let sacrificial = (prefix_len + align) / block_size;
+            let input = vec![PLACEHOLDER; align + block_size + target.len()];
+            let at = (sacrificial + 1) * block_size;
+            (input, at, sacrificial * block_size, vec![sacrificial])
+        }
+        CipherMode::ECB => return Err("bitflip: ECB can't be bitflipped".to_string()),
+    };
+
+    let mut ciphertext = encrypt(&input);
+    if ciphertext.len() < at + target.len() {
+        return Err("bitflip: ciphertext shorter than expected".to_string());
+    }
+    for (i, t) in target.iter().enumerate() {
+        ciphertext[edit + i] ^= PLACEHOLDER ^ t;
+    }
+
+    if check(&ciphertext) {
+        Ok(Flip {
+            ciphertext,
+            scrambled,
+        })
+    } else {
+        Err("bitflip: the edited ciphertext was rejected".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject, Server};
+    use crate::aes::{self, CipherMode};
+
+    const TARGET: &[u8] = b";admin=true;";
+
+    #[test]
+    fn test_escaping() {
+        for &mode in [CipherMode::CBC, CipherMode::CTR].iter() {
+            let server = Server::new(mode).unwrap();
+            let ct = server.encrypt(TARGET);
+            assert!(!server.is_admin(&ct));
+            let pt = server.decrypt(&ct).unwrap();
+            assert!(pt.starts_with(Server::PREFIX));
+            assert!(pt.ends_with(Server::SUFFIX));
+        }
+    }
+
+    #[test]
+    fn test_inject() {
+        for &mode in [CipherMode::CBC, CipherMode::CTR].iter() {
+            let server = Server::new(mode).unwrap();
+            let flip = inject(
+                |data: &[u8]| server.encrypt(data),
+                |ct: &[u8]| server.is_admin(ct),
+                mode,
+                aes::KEY_SIZE,
+                Server::PREFIX.len(),
+                TARGET,
+            )
+            .unwrap();
+
+            let pt = server.decrypt(&flip.ciphertext).unwrap();
+            let expected: &[usize] = if mode == CipherMode::CBC { &[2] } else { &[] };
+            assert_eq!(flip.scrambled, expected);
+            // every block but the scrambled ones is what the attacker expects
+            let placeholder: Vec<u8> = pt
+                .chunks(aes::KEY_SIZE)
+                .enumerate()
+                .filter(|(i, _)| !flip.scrambled.contains(i))
+                .flat_map(|(_, b)| b.iter().cloned())
+                .collect();
+            assert!(placeholder.windows(TARGET.len()).any(|w| w == TARGET));
+        }
+    }
+
+    #[test]
+    fn test_inject_errors() {
+        assert!(Server::new(CipherMode::ECB).is_err());
+        let server = Server::new(CipherMode::CBC).unwrap();
+        let encrypt = |data: &[u8]| server.encrypt(data);
+        let check = |ct: &[u8]| server.is_admin(ct);
+        let long = b";admin=true;and=a-lot-more";
+        assert!(inject(encrypt, check, CipherMode::CBC, 16, 32, long).is_err());
+        assert!(inject(encrypt, check, CipherMode::ECB, 16, 32, TARGET).is_err());
+        // wrong prefix length: the flip lands somewhere else and the check fails
+        assert!(inject(encrypt, check, CipherMode::CBC, 16, 0, TARGET).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Byte-at-a-time ECB decryption (Cryptopals challenges 12 and 14): recover the secret an
+//! oracle appends to our input before encrypting it under ECB, with or without random bytes
+//! in front of the input.
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+/// Largest block size looked for.
+pub const MAX_BLOCK_SIZE: usize = 64;
+
+/// How many oracle calls to spend getting our input to start on a block boundary.
+const MAX_ALIGN_TRIES: usize = 1024;
+
+/// The block size of the cipher behind an oracle: every ciphertext is a whole number of
+/// blocks, and as the input grows the length goes up one block at a time. This holds even
+/// if the oracle prepends a different number of bytes each call.
+pub fn detect_block_size<F: Fn(&[u8]) -> Vec<u8>>(oracle: &F) -> Result<usize, String> {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            let t = a % b;
+            a = b;
+            b = t;
+        }
+        a
+    };
+    let size = (0..=2 * MAX_BLOCK_SIZE)
+        .map(|n| oracle(&vec![0u8; n]).len())
+        .fold(0, gcd);
+    if (2..=MAX_BLOCK_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "byte_at_a_time: no block size, lengths share {}",
+            size
+        ))
+    }
+}
+
+/// Feed the oracle three blocks' worth of identical bytes: under ECB, at least two whole
+/// blocks of them encrypt the same whatever comes before.
+pub fn is_ecb<F: Fn(&[u8]) -> Vec<u8>>(oracle: &F, block_size: usize) -> bool {
+    let ct = oracle(&vec![0u8; 3 * block_size]);
+    let mut seen = HashSet::new();
+    ct.chunks(block_size).any(|block| !seen.insert(block))
+}
+
+pub struct ByteAtATime<F> {
+    oracle: F,
+    block_size: usize,
+    // one less than the filler length that last got the marker aligned, to start from
+    // next time
+    filler: Cell<usize>,
+}
+
+impl<F: Fn(&[u8]) -> Vec<u8>> ByteAtATime<F> {
+    /// Detect the block size and check the oracle really is ECB.
+    pub fn new(oracle: F) -> Result<Self, String> {
+        let block_size = detect_block_size(&oracle)?;
+        if !is_ecb(&oracle, block_size) {
+            return Err("byte_at_a_time: oracle is not ECB".to_string());
+        }
+        Ok(ByteAtATime {
+            oracle,
+            block_size,
+            filler: Cell::new(0),
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Query the oracle so that `input` starts on a block boundary, and return the prefix
+    /// length of that call along with the ciphertext from `input` on.
+    ///
+    /// `input` goes after some filler and two copies of a marker block. The marker shows up
+    /// as two identical ciphertext blocks only when it is aligned, which tells us where our
+    /// input starts. With a prefix of fixed length the right filler is found once; with a
+    /// prefix that changes every call we retry until one lines up. The filler is never
+    /// empty, so a prefix that happens to end like the marker can't fake the repeat: the
+    /// byte before the marker is always a zero, which the marker doesn't end with.
+    fn aligned(&self, input: &[u8]) -> Result<(usize, Vec<u8>), String> {
+        let bs = self.block_size;
+        let marker: Vec<u8> = (0..bs as u8).map(|b| b.wrapping_mul(37) ^ 0xa5).collect();
+        debug_assert_ne!(marker[bs - 1], 0);
+        for attempt in 0..MAX_ALIGN_TRIES {
+            let filler = (self.filler.get() + attempt) % bs + 1;
+            let mut query = vec![0u8; filler];
+            query.extend_from_slice(&marker);
+            query.extend_from_slice(&marker);
+            query.extend_from_slice(input);
+
+            let ct = (self.oracle)(&query);
+            let blocks: Vec<&[u8]> = ct.chunks(bs).collect();
+            if let Some(i) =
+                (0..blocks.len().saturating_sub(1)).find(|&i| blocks[i] == blocks[i + 1])
+            {
+                if i * bs >= filler {
+                    self.filler.set(filler - 1);
+                    return Ok((i * bs - filler, ct[(i + 2) * bs..].to_vec()));
+                }
+            }
+        }
+        Err("byte_at_a_time: could not align input to a block".to_string())
+    }
+
+    /// Length of the bytes the oracle puts before our input. If that changes from call to
+    /// call, this is the length in one of them.
+    pub fn prefix_len(&self) -> Result<usize, String> {
+        Ok(self.aligned(&[])?.0)
+    }
+
+    /// Length of the secret the oracle appends, from the input length at which the
+    /// ciphertext grows by a block.
+    pub fn secret_len(&self) -> Result<usize, String> {
+        let base = self.aligned(&[])?.1.len();
+        for n in 1..=self.block_size {
+            let len = self.aligned(&vec![0u8; n])?.1.len();
+            if len > base {
+                return Ok(base - n);
+            }
+        }
+        Err("byte_at_a_time: ciphertext never grew".to_string())
+    }
+
+    /// Recover the secret one byte at a time. Shift it so that the next unknown byte is the
+    /// last of a block whose other bytes we know, then match that block against a dictionary
+    /// of all 256 possible endings, built from a single query.
+    pub fn recover_secret(&self) -> Result<Vec<u8>, String> {
+        let bs = self.block_size;
+        let len = self.secret_len()?;
+        let mut secret: Vec<u8> = Vec::with_capacity(len);
+        while secret.len() < len {
+            let i = secret.len();
+            let pad = vec![0u8; bs - 1 - i % bs];
+
+            // the block ending in secret[i], with the bs - 1 bytes before it known
+            let (_, ct) = self.aligned(&pad)?;
+            let block = i / bs;
+            let target = &ct[block * bs..(block + 1) * bs];
+
+            let known: Vec<u8> = pad.iter().chain(secret.iter()).cloned().collect();
+            let context = &known[known.len() - (bs - 1)..];
+            let mut query = Vec::with_capacity(256 * bs);
+            for b in 0..=255u8 {
+                query.extend_from_slice(context);
+                query.push(b);
+            }
+            let (_, ct) = self.aligned(&query)?;
+            let dictionary: HashMap<&[u8], u8> = ct.chunks(bs).take(256).zip(0..=255u8).collect();
+
+            match dictionary.get(target) {
+                Some(&b) => secret.push(b),
+                None => return Err(format!("byte_at_a_time: no match for byte {}", i)),
+            }
+        }
+        Ok(secret)
+    }
+}
+
+/// Recover the secret behind an ECB oracle.
+pub fn attack<F: Fn(&[u8]) -> Vec<u8>>(oracle: F) -> Result<Vec<u8>, String> {
+    ByteAtATime::new(oracle)?.recover_secret()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attack, detect_block_size, is_ecb, ByteAtATime};
+    use crate::aes;
+    use crate::rand::{self, Rng, SeedableRng, Xoshiro256};
+    use std::cell::RefCell;
+
+    const SECRET: &[u8] = b"Rollin' in my 5.0\nWith my rag-top down so my hair can blow\n";
+
+    fn ecb_oracle(prefix: Vec<u8>) -> impl Fn(&[u8]) -> Vec<u8> {
+        let key = rand::bytes(aes::KEY_SIZE as u64);
+        move |input: &[u8]| {
+            let pt: Vec<u8> = prefix
+                .iter()
+                .chain(input.iter())
+                .chain(SECRET.iter())
+                .cloned()
+                .collect();
+            aes::encrypt_ecb(&pt, &key)
+        }
+    }
+
+    #[test]
+    fn test_no_prefix() {
+        let oracle = ecb_oracle(vec![]);
+        assert_eq!(detect_block_size(&oracle), Ok(aes::KEY_SIZE));
+        assert!(is_ecb(&oracle, aes::KEY_SIZE));
+
+        let breaker = ByteAtATime::new(oracle).unwrap();
+        assert_eq!(breaker.prefix_len(), Ok(0));
+        assert_eq!(breaker.secret_len(), Ok(SECRET.len()));
+        assert_eq!(breaker.recover_secret().unwrap(), SECRET);
+    }
+
+    #[test]
+    fn test_fixed_prefix() {
+        for &n in [1u64, 15, 16, 37].iter() {
+            let oracle = ByteAtATime::new(ecb_oracle(rand::bytes(n))).unwrap();
+            assert_eq!(oracle.prefix_len(), Ok(n as usize));
+            assert_eq!(oracle.secret_len(), Ok(SECRET.len()));
+        }
+        assert_eq!(attack(ecb_oracle(rand::bytes(21))).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn test_prefix_ending_like_marker() {
+        // a prefix whose last byte matches the marker's used to pass for no prefix at all
+        let bs = aes::KEY_SIZE as u8;
+        let last = (bs - 1).wrapping_mul(37) ^ 0xa5;
+        let oracle = ByteAtATime::new(ecb_oracle(vec![last])).unwrap();
+        assert_eq!(oracle.prefix_len(), Ok(1));
+        assert_eq!(oracle.secret_len(), Ok(SECRET.len()));
+    }
+
+    #[test]
+    fn test_random_prefix_every_call() {
+        let key = rand::bytes(aes::KEY_SIZE as u64);
+        let rng = RefCell::new(Xoshiro256::seed_from_u64(14));
+        let oracle = |input: &[u8]| {
+            let mut rng = rng.borrow_mut();
+            let n = rng.range(40);
+            let pt: Vec<u8> = rng
+                .get_bytes(n)
+                .iter()
+                .chain(input.iter())
+                .chain(SECRET.iter())
+                .cloned()
+                .collect();
+            aes::encrypt_ecb(&pt, &key)
+        };
+        assert_eq!(attack(oracle).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn test_not_ecb() {
+        let key = rand::bytes(aes::KEY_SIZE as u64);
+        let cbc = |input: &[u8]| aes::encrypt_cbc(input, &key, &[0u8; 16]);
+        assert!(attack(cbc).is_err());
+        let ctr = |input: &[u8]| aes::encrypt_ctr(input, &key, &[0u8; 8]);
+        assert!(detect_block_size(&ctr).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! ECB cut-and-paste (Cryptopals challenge 13): splice ciphertext blocks from different
+//! encryptions of a user profile into one that decrypts to a forged field.
+use crate::aes;
+use crate::kv::{self, Codec, Escaping, Field};
+use crate::pad;
+use crate::rand;
+
+/// Stand-in for a service that hands out encrypted profile cookies.
+pub struct ProfileServer {
+    key: Vec<u8>,
+    codec: Codec,
+}
+
+impl ProfileServer {
+    /// A server with a random key, encoding profiles as `email=..&uid=10&role=user` and
+    /// stripping metacharacters from the email.
+    pub fn new() -> Self {
+        ProfileServer::with_codec(Codec::query_string(Escaping::Strip))
+    }
+
+    pub fn with_codec(codec: Codec) -> Self {
+        ProfileServer {
+            key: rand::bytes(aes::KEY_SIZE as u64),
+            codec,
+        }
+    }
+
+    /// The encoded profile for an email address, before encryption.
+    pub fn encode_profile(&self, email: &str) -> Vec<u8> {
+        self.codec
+            .encode(&[("email", email), ("uid", "10"), ("role", "user")])
+    }
+
+    /// The oracle: an encrypted profile cookie for any email.
+    pub fn profile_for(&self, email: &str) -> Vec<u8> {
+        aes::encrypt_ecb(&self.encode_profile(email), &self.key)
+    }
+
+    /// Decrypt and parse a cookie.
+    pub fn decrypt_profile(&self, ct: &[u8]) -> Result<Vec<Field>, String> {
+        let pt = aes::decrypt_ecb(ct, &self.key).map_err(|e| e.to_string())?;
+        self.codec.decode(&pt)
+    }
+
+    /// The role in a cookie, if it decrypts and has one.
+    pub fn role(&self, ct: &[u8]) -> Option<String> {
+        let fields = self.decrypt_profile(ct).ok()?;
+        kv::get(&fields, "role").map(|r| String::from_utf8_lossy(r).into_owned())
+    }
+}
+
+impl Default for ProfileServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where our input lands in the encoded plaintext: the offset of the first byte, found by
+/// feeding runs of identical bytes until two ciphertext blocks repeat.
+fn input_offset<F: Fn(&str) -> Vec<u8>>(oracle: &F, block_size: usize) -> Result<usize, String> {
+    for filler in 0..block_size {
+        let ct = oracle(&"A".repeat(filler + 2 * block_size));
+        let blocks: Vec<&[u8]> = ct.chunks(block_size).collect();
+        if let Some(i) = (1..blocks.len()).find(|&i| blocks[i - 1] == blocks[i]) {
+            return Ok((i - 1) * block_size - filler);
+        }
+    }
+    Err("cut_paste: could not find the input in the ciphertext".to_string())
+}
+
+/// Number of bytes the encoding adds after our input, from the input length at which the
+/// ciphertext grows by a block.
+fn suffix_len<F: Fn(&str) -> Vec<u8>>(
+    oracle: &F,
+    block_size: usize,
+    offset: usize,
+) -> Result<usize, String> {
+    let base = oracle("").len();
+    (1..=block_size)
+        .find(|&n| oracle(&"A".repeat(n)).len() > base)
+        .map(|n| base - n - offset)
+        .ok_or_else(|| "cut_paste: ciphertext never grew".to_string())
+}
+
+/// Forge a cookie whose `key` field reads `value`. Works with any ECB oracle that places our
+/// input somewhere before that field and encodes it as is; stripping or escaping
+/// metacharacters doesn't stop it. `after` is the encoding the attacker expects after its
+/// input, such as `&uid=10&role=user`; escaping keeps it from being read back blind, so it
+/// is only checked against the probed length. Any fields after `key` are cut off.
+///
+/// One query puts `value` and its padding at the start of a block; another pushes the
+/// encoding so the old value starts a block of its own. Swapping everything from that block
+/// on for the first gives the forgery.
+pub fn forge<F: Fn(&str) -> Vec<u8>>(
+    oracle: F,
+    block_size: usize,
+    after: &str,
+    key: &str,
+    value: &str,
+) -> Result<Vec<u8>, String> {
+    let offset = input_offset(&oracle, block_size)?;
+    let suffix = suffix_len(&oracle, block_size, offset)?;
+    if after.len() != suffix {
+        return Err(format!(
+            "cut_paste: {} bytes follow our input, not {}",
+            suffix,
+            after.len()
+        ));
+    }
+    let field = format!("{}=", key);
+    let at = after
+        .match_indices(&field)
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || !after.as_bytes()[i - 1].is_ascii_alphanumeric())
+        .ok_or_else(|| format!("cut_paste: no {} field after our input", key))?;
+
+    // block-aligned `value` with PKCS#7 padding, as if it ended the plaintext
+    let align = (block_size - offset % block_size) % block_size;
+    let padded = pad::pad_block(value.as_bytes(), block_size);
+    let padded = std::str::from_utf8(&padded).map_err(|_| {
+        format!(
+            "cut_paste: padding for {}-byte blocks isn't text",
+            block_size
+        )
+    })?;
+    let input = "A".repeat(align) + padded;
+    let start = offset + align;
+    let forged_tail = oracle(&input)[start..start + padded.len()].to_vec();
+
+    // everything up to the old value, ending on a block boundary
+    let head_len = offset + at + field.len();
+    let n = (block_size - head_len % block_size) % block_size;
+    let ct = oracle(&"A".repeat(n));
+    let mut forged = ct[..head_len + n].to_vec();
+    forged.extend_from_slice(&forged_tail);
+    Ok(forged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{forge, ProfileServer};
+    use crate::aes;
+    use crate::kv::{self, Codec, Escaping};
+    use crate::pad;
+
+    const AFTER: &str = "&uid=10&role=user";
+
+    #[test]
+    fn test_profile_for() {
+        let server = ProfileServer::new();
+        assert_eq!(
+            server.encode_profile("foo@bar.com&role=admin"),
+            b"email=foo@bar.comroleadmin&uid=10&role=user"
+        );
+        let cookie = server.profile_for("foo@bar.com");
+        let fields = server.decrypt_profile(&cookie).unwrap();
+        assert_eq!(kv::get(&fields, "email"), Some(&b"foo@bar.com"[..]));
+        assert_eq!(server.role(&cookie), Some("user".to_string()));
+    }
+
+    #[test]
+    fn test_forge() {
+        for &escaping in [Escaping::Strip, Escaping::Percent].iter() {
+            let server = ProfileServer::with_codec(Codec::query_string(escaping));
+            let oracle = |email: &str| server.profile_for(email);
+            for value in ["admin", "superuser-with-a-long-name"].iter() {
+                let cookie = forge(oracle, aes::KEY_SIZE, AFTER, "role", value).unwrap();
+                assert_eq!(server.role(&cookie), Some(value.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_forge_other_fields() {
+        // role first: the uid after it is cut off
+        let codec = Codec::query_string(Escaping::Strip);
+        let server = ProfileServer::with_codec(codec);
+        let oracle = |email: &str| {
+            let pt = codec.encode(&[("email", email), ("role", "user"), ("uid", "10")]);
+            aes::encrypt_ecb(&pt, &server.key)
+        };
+        let cookie = forge(oracle, aes::KEY_SIZE, "&role=user&uid=10", "role", "admin").unwrap();
+        let fields = server.decrypt_profile(&cookie).unwrap();
+        assert_eq!(kv::get(&fields, "role"), Some(&b"admin"[..]));
+        assert_eq!(kv::get(&fields, "uid"), None);
+
+        let oracle = |email: &str| server.profile_for(email);
+        let cookie = forge(oracle, aes::KEY_SIZE, AFTER, "uid", "0").unwrap();
+        let fields = server.decrypt_profile(&cookie).unwrap();
+        assert_eq!(kv::get(&fields, "uid"), Some(&b"0"[..]));
+
+        // not after our input, not a whole key, or the wrong layout
+        assert!(forge(oracle, aes::KEY_SIZE, AFTER, "email", "x").is_err());
+        assert!(forge(oracle, aes::KEY_SIZE, AFTER, "ole", "x").is_err());
+        assert!(forge(oracle, aes::KEY_SIZE, "&role=user", "role", "x").is_err());
+    }
+
+    #[test]
+    fn test_forge_large_blocks() {
+        // padding "admin" to 200 bytes takes bytes of 195, which no UTF-8 input can
+        // carry; a cipher that does nothing is enough to get that far
+        let oracle = |email: &str| {
+            let pt = format!("email={}{}", email, AFTER);
+            pad::pad_block(pt.as_bytes(), 200)
+        };
+        let err = forge(oracle, 200, AFTER, "role", "admin").unwrap_err();
+        assert!(err.contains("isn't text"), "{}", err);
+    }
+}
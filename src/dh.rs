@@ -0,0 +1,422 @@
+//! Diffie-Hellman key exchange, and an in-process harness for running it between Alice and
+//! Bob with an optional man in the middle (Cryptopals challenges 33-35).
+use crate::aes;
+use crate::bigint::BigUint;
+use crate::pad::PaddingError;
+use crate::rand::Rng;
+use crate::sha1;
+
+/// The 1536-bit MODP group from RFC 3526, also the "NIST" prime used by Cryptopals.
+const MODP_1536: &str = "\
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74\
+    020bbea63b139b22514a08798e3404ddef9519b3cd3a431b302b0a6df25f1437\
+    4fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6f406b7ed\
+    ee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf05\
+    98da48361c55d39a69163fa8fd24cf5f83655d23dca3ad961c62f356208552bb\
+    9ed529077096966d670c354e4abc9804f1746c08ca237327ffffffffffffffff";
+
+/// The 2048-bit MODP group from RFC 3526.
+const MODP_2048: &str = "\
+    ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74\
+    020bbea63b139b22514a08798e3404ddef9519b3cd3a431b302b0a6df25f1437\
+    4fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6f406b7ed\
+    ee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf05\
+    98da48361c55d39a69163fa8fd24cf5f83655d23dca3ad961c62f356208552bb\
+    9ed529077096966d670c354e4abc9804f1746c08ca18217c32905e462e36ce3b\
+    e39e772c180e86039b2783a2ec07a28fb5c55df06f4c52c9de2bcbf695581718\
+    3995497cea956ae515d2261898fa051015728e5a8aacaa68ffffffffffffffff";
+
+/// Public parameters: a prime modulus p and a generator g.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub p: BigUint,
+    pub g: BigUint,
+}
+
+impl Group {
+    pub fn new(p: BigUint, g: BigUint) -> Self {
+        Group { p, g }
+    }
+
+    /// RFC 3526 1536-bit group with g = 2.
+    pub fn nist() -> Self {
+        Group::new(BigUint::from_hex(MODP_1536).unwrap(), BigUint::from(2u64))
+    }
+
+    /// RFC 3526 2048-bit group with g = 2.
+    pub fn rfc3526_2048() -> Self {
+        Group::new(BigUint::from_hex(MODP_2048).unwrap(), BigUint::from(2u64))
+    }
+}
+
+pub struct Keypair {
+    private: BigUint,
+    pub public: BigUint,
+}
+
+impl Keypair {
+    /// A fresh keypair, with the private key drawn from [2, p - 2] as the FFDH specs ask.
+    pub fn generate<R: Rng>(group: &Group, rng: &mut R) -> Self {
+        assert!(group.p > BigUint::from(4u64), "dh: p too small for a keypair");
+        let private = BigUint::random_below(rng, &(&group.p - 3u64)) + 2u64;
+        let public = group.g.modpow(&private, &group.p);
+        Keypair { private, public }
+    }
+
+    pub fn shared_secret(&self, group: &Group, other: &BigUint) -> BigUint {
+        other.modpow(&self.private, &group.p)
+    }
+}
+
+/// Turn a shared secret into an AES-128 key: the first 16 bytes of its SHA-1.
+pub fn derive_key(secret: &BigUint) -> Vec<u8> {
+    sha1::digest(&secret.to_bytes_be())[..aes::KEY_SIZE].to_vec()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Alice proposes the group.
+    Params {
+        p: BigUint,
+        g: BigUint,
+    },
+    /// Bob agrees to it.
+    Ack {
+        p: BigUint,
+        g: BigUint,
+    },
+    PublicKey(BigUint),
+    /// AES-CBC ciphertext and the IV it was encrypted with.
+    Data {
+        ciphertext: Vec<u8>,
+        iv: Vec<u8>,
+    },
+}
+
+/// One side of the exchange. Alice and Bob only differ in who speaks first.
+struct Party {
+    group: Option<Group>,
+    keypair: Option<Keypair>,
+    secret: Option<BigUint>,
+    key: Option<Vec<u8>>,
+}
+
+impl Party {
+    fn new() -> Self {
+        Party {
+            group: None,
+            keypair: None,
+            secret: None,
+            key: None,
+        }
+    }
+
+    fn agree<R: Rng>(&mut self, p: BigUint, g: BigUint, rng: &mut R) -> BigUint {
+        let group = Group::new(p, g);
+        let keypair = Keypair::generate(&group, rng);
+        let public = keypair.public.clone();
+        self.group = Some(group);
+        self.keypair = Some(keypair);
+        public
+    }
+
+    fn receive_key(&mut self, other: &BigUint) {
+        let (group, keypair) = (self.group.as_ref(), self.keypair.as_ref());
+        let secret = keypair.unwrap().shared_secret(group.unwrap(), other);
+        self.key = Some(derive_key(&secret));
+        self.secret = Some(secret);
+    }
+
+    fn seal<R: Rng>(&self, bytes: &[u8], rng: &mut R) -> Message {
+        let iv = rng.get_bytes(aes::KEY_SIZE as u64);
+        let ciphertext = aes::encrypt_cbc(bytes, self.key.as_ref().unwrap(), &iv);
+        Message::Data { ciphertext, iv }
+    }
+
+    fn open(&self, msg: Message) -> Result<Vec<u8>, PaddingError> {
+        match msg {
+            Message::Data { ciphertext, iv } => {
+                aes::decrypt_cbc(&ciphertext, self.key.as_ref().unwrap(), &iv)
+            }
+            _ => panic!("dh: expected data, got {:?}", msg),
+        }
+    }
+}
+
+/// How far Mallory will walk powers of g looking for a private key under a substituted p.
+const MAX_DLOG_STEPS: u64 = 1 << 20;
+
+/// Rewrite g to one of these to make the shared secret predictable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaliciousG {
+    One,
+    P,
+    PMinusOne,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attack {
+    /// Swap both public keys for p, so both sides end up with s = 0 (challenge 34).
+    KeyFixing,
+    /// Swap g in the negotiation for a degenerate value (challenge 35).
+    NegotiatedG(MaliciousG),
+    /// Swap p in the negotiation for a small modulus, where the discrete log of either public
+    /// key can be found by brute force.
+    ReplaceP(BigUint),
+}
+
+/// A man in the middle that rewrites messages and reads the traffic.
+pub struct Mallory {
+    attack: Attack,
+    p: Option<BigUint>,
+    g: Option<BigUint>,
+    public_keys: Vec<BigUint>,
+    /// Every plaintext Mallory managed to read, in order.
+    pub recovered: Vec<Vec<u8>>,
+}
+
+impl Mallory {
+    pub fn new(attack: Attack) -> Self {
+        Mallory {
+            attack,
+            p: None,
+            g: None,
+            public_keys: vec![],
+            recovered: vec![],
+        }
+    }
+
+    fn intercept(&mut self, msg: Message) -> Message {
+        match msg {
+            Message::Params { p, g } => {
+                let p = self.rewrite_p(p);
+                let g = self.rewrite_g(&p, g);
+                self.p = Some(p.clone());
+                self.g = Some(g.clone());
+                Message::Params { p, g }
+            }
+            Message::Ack { p, g } => {
+                let p = self.rewrite_p(p);
+                let g = self.rewrite_g(&p, g);
+                Message::Ack { p, g }
+            }
+            Message::PublicKey(key) => {
+                let key = match self.attack {
+                    Attack::KeyFixing => self.p.clone().unwrap(),
+                    Attack::NegotiatedG(_) | Attack::ReplaceP(_) => key,
+                };
+                self.public_keys.push(key.clone());
+                Message::PublicKey(key)
+            }
+            Message::Data { ciphertext, iv } => {
+                if let Some(secret) = self.predict_secret() {
+                    let key = derive_key(&secret);
+                    if let Ok(pt) = aes::decrypt_cbc(&ciphertext, &key, &iv) {
+                        self.recovered.push(pt);
+                    }
+                }
+                Message::Data { ciphertext, iv }
+            }
+        }
+    }
+
+    fn rewrite_p(&self, p: BigUint) -> BigUint {
+        match &self.attack {
+            Attack::ReplaceP(q) => q.clone(),
+            _ => p,
+        }
+    }
+
+    fn rewrite_g(&self, p: &BigUint, g: BigUint) -> BigUint {
+        match self.attack {
+            Attack::NegotiatedG(MaliciousG::One) => BigUint::one(),
+            Attack::NegotiatedG(MaliciousG::P) => p.clone(),
+            Attack::NegotiatedG(MaliciousG::PMinusOne) => p - 1,
+            Attack::KeyFixing | Attack::ReplaceP(_) => g,
+        }
+    }
+
+    /// The secret both sides must have computed, given what Mallory did to the exchange, if
+    /// she can work it out.
+    fn predict_secret(&self) -> Option<BigUint> {
+        let p = self.p.as_ref().unwrap();
+        let secret = match self.attack {
+            Attack::KeyFixing | Attack::NegotiatedG(MaliciousG::P) => BigUint::zero(),
+            Attack::NegotiatedG(MaliciousG::One) => BigUint::one(),
+            // (p - 1)^(ab) is p - 1 only if both a and b are odd, i.e. both public keys are p - 1
+            Attack::NegotiatedG(MaliciousG::PMinusOne) => {
+                let minus_one = p - 1;
+                if self.public_keys.iter().all(|k| *k == minus_one) {
+                    minus_one
+                } else {
+                    BigUint::one()
+                }
+            }
+            // Alice's private key from her public key, then the secret as Bob's key to it
+            Attack::ReplaceP(_) => {
+                let (alice, bob) = (self.public_keys.first()?, self.public_keys.get(1)?);
+                let a = small_dlog(self.g.as_ref().unwrap(), alice, p)?;
+                bob.modpow(&a, p)
+            }
+        };
+        Some(secret)
+    }
+}
+
+/// Find x with g^x = y mod p by trying every x in turn.
+fn small_dlog(g: &BigUint, y: &BigUint, p: &BigUint) -> Option<BigUint> {
+    let g = g % p;
+    let mut power = BigUint::one() % p;
+    for x in 0..MAX_DLOG_STEPS {
+        if power == *y {
+            return Some(BigUint::from(x));
+        }
+        power = &power * &g % p;
+    }
+    None
+}
+
+/// What each party computed and read in an exchange.
+pub struct Transcript {
+    /// The shared secrets Alice and Bob derived.
+    pub alice_secret: BigUint,
+    pub bob_secret: BigUint,
+    /// The message as decrypted by Bob.
+    pub bob_received: Vec<u8>,
+    /// Bob's echo as decrypted by Alice.
+    pub alice_received: Vec<u8>,
+}
+
+/// Alice negotiates a group with Bob, they exchange keys, Alice sends a message encrypted with
+/// AES-CBC under the derived key and Bob echoes it back. Everything goes through Mallory, if
+/// there is one.
+pub fn run_exchange<R: Rng>(
+    group: &Group,
+    message: &[u8],
+    mut mallory: Option<&mut Mallory>,
+    rng: &mut R,
+) -> Result<Transcript, PaddingError> {
+    let mut wire = |msg: Message| match mallory.as_mut() {
+        Some(m) => m.intercept(msg),
+        None => msg,
+    };
+    let mut alice = Party::new();
+    let mut bob = Party::new();
+
+    // Mallory never changes the kind of a message, only its contents
+    let (p, g) = match wire(Message::Params {
+        p: group.p.clone(),
+        g: group.g.clone(),
+    }) {
+        Message::Params { p, g } => (p, g),
+        _ => unreachable!(),
+    };
+    let bob_public = bob.agree(p.clone(), g.clone(), rng);
+    let alice_public = match wire(Message::Ack { p, g }) {
+        Message::Ack { p, g } => alice.agree(p, g, rng),
+        _ => unreachable!(),
+    };
+
+    if let Message::PublicKey(key) = wire(Message::PublicKey(alice_public)) {
+        bob.receive_key(&key);
+    }
+    if let Message::PublicKey(key) = wire(Message::PublicKey(bob_public)) {
+        alice.receive_key(&key);
+    }
+
+    let bob_received = bob.open(wire(alice.seal(message, rng)))?;
+    let alice_received = alice.open(wire(bob.seal(&bob_received, rng)))?;
+
+    Ok(Transcript {
+        alice_secret: alice.secret.unwrap(),
+        bob_secret: bob.secret.unwrap(),
+        bob_received,
+        alice_received,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_exchange, Attack, Group, Keypair, MaliciousG, Mallory};
+    use crate::bigint::BigUint;
+    use crate::rand::{SeedableRng, Xoshiro256};
+
+    #[test]
+    fn test_shared_secret() {
+        let mut rng = Xoshiro256::seed_from_u64(33);
+        let small = Group::new(BigUint::from(37u64), BigUint::from(5u64));
+        for group in [small, Group::nist()].iter() {
+            let a = Keypair::generate(group, &mut rng);
+            let b = Keypair::generate(group, &mut rng);
+            assert_eq!(
+                a.shared_secret(group, &b.public),
+                b.shared_secret(group, &a.public)
+            );
+        }
+        assert_eq!(Group::rfc3526_2048().p.bits(), 2048);
+    }
+
+    #[test]
+    fn test_private_key_range() {
+        let mut rng = Xoshiro256::seed_from_u64(35);
+        let group = Group::new(BigUint::from(7u64), BigUint::from(3u64));
+        let mut seen = [false; 7];
+        for _ in 0..200 {
+            let keypair = Keypair::generate(&group, &mut rng);
+            let x = keypair.private.to_bytes_be().last().cloned().unwrap_or(0) as usize;
+            assert!((2..=5).contains(&x), "private key {}", x);
+            seen[x] = true;
+        }
+        assert_eq!(seen, [false, false, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_exchange() {
+        let mut rng = Xoshiro256::seed_from_u64(34);
+        let msg = b"attack at dawn";
+
+        let transcript = run_exchange(&Group::nist(), msg, None, &mut rng).unwrap();
+        assert_eq!(transcript.alice_secret, transcript.bob_secret);
+        assert_eq!(transcript.bob_received, msg);
+        assert_eq!(transcript.alice_received, msg);
+
+        let mut mallory = Mallory::new(Attack::KeyFixing);
+        let transcript = run_exchange(&Group::nist(), msg, Some(&mut mallory), &mut rng).unwrap();
+        assert_eq!(transcript.alice_received, msg);
+        assert_eq!(mallory.recovered, vec![msg.to_vec(), msg.to_vec()]);
+    }
+
+    #[test]
+    fn test_malicious_g() {
+        let mut rng = Xoshiro256::seed_from_u64(35);
+        let msg = b"attack at dusk";
+        let attacks = [MaliciousG::One, MaliciousG::P, MaliciousG::PMinusOne];
+        for &g in attacks.iter() {
+            // several runs so p - 1 hits both of its possible secrets
+            for _ in 0..4 {
+                let mut mallory = Mallory::new(Attack::NegotiatedG(g));
+                let group = Group::nist();
+                let transcript = run_exchange(&group, msg, Some(&mut mallory), &mut rng).unwrap();
+                assert_eq!(transcript.alice_received, msg);
+                assert_eq!(mallory.recovered, vec![msg.to_vec(), msg.to_vec()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replace_p() {
+        let mut rng = Xoshiro256::seed_from_u64(36);
+        let msg = b"attack at noon";
+        let small = BigUint::from(65_537u64);
+        for _ in 0..3 {
+            let mut mallory = Mallory::new(Attack::ReplaceP(small.clone()));
+            let transcript =
+                run_exchange(&Group::nist(), msg, Some(&mut mallory), &mut rng).unwrap();
+            // both sides did the exchange mod the small p, and still agree
+            assert_eq!(transcript.alice_secret, transcript.bob_secret);
+            assert!(transcript.alice_secret < small);
+            assert_eq!(transcript.alice_received, msg);
+            assert_eq!(mallory.recovered, vec![msg.to_vec(), msg.to_vec()]);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! DSA over configurable parameters, and recovery of the private key from bad nonces
+//! (Cryptopals challenges 43 and 44).
+use std::io::BufRead;
+use std::ops::Range;
+
+use crate::bigint::BigUint;
+use crate::hex;
+use crate::rand::Rng;
+use crate::sha1;
+
+/// The 1024-bit parameters from Cryptopals challenge 43.
+const P: &str = "\
+    800000000000000089e1855218a0e7dac38136ffafa72eda7859f2171e25e65e\
+    ac698c1702578b07dc2a1076da241c76c62d374d8389ea5aeffd3226a0530cc5\
+    65f3bf6b50929139ebeac04f48c3c84afb796d61e5a4f9a8fda812ab59494232\
+    c7d2b4deb50aa18ee9e132bfa85ac4374d7f9091abc3d015efc871a584471bb1";
+const Q: &str = "f4f47f05794b256174bba6e9b396a7707e563c5b";
+const G: &str = "\
+    5958c9d3898b224b12672c0b98e06c60df923cb8bc999d119458fef538b8fa40\
+    46c8db53039db620c094c9fa077ef389b5322a559946a71903f990f1f7e0e025\
+    e2d7f7cf494aff1a0470f5b64c36b625a097f1651fe775323556fe00b3608c88\
+    7892878480e99041be601a62166ca6894bdd41a7054ec89f756ba9fc95302291";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Params {
+    pub p: BigUint,
+    pub q: BigUint,
+    pub g: BigUint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub r: BigUint,
+    pub s: BigUint,
+}
+
+pub struct Keypair {
+    pub x: BigUint,
+    pub y: BigUint,
+}
+
+/// A message and its signature, as listed in the challenge 44 data file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedMessage {
+    pub msg: String,
+    pub signature: Signature,
+    /// SHA-1 of the message, as an integer.
+    pub m: BigUint,
+}
+
+/// (a - b) mod q, for a and b already below q.
+fn sub_mod(a: &BigUint, b: &BigUint, q: &BigUint) -> BigUint {
+    &(&(a + q) - b) % q
+}
+
+impl Params {
+    pub fn new(p: BigUint, q: BigUint, g: BigUint) -> Self {
+        Params { p, q, g }
+    }
+
+    pub fn cryptopals() -> Self {
+        Params::new(
+            BigUint::from_hex(P).unwrap(),
+            BigUint::from_hex(Q).unwrap(),
+            BigUint::from_hex(G).unwrap(),
+        )
+    }
+
+    pub fn generate_keypair<R: Rng>(&self, rng: &mut R) -> Keypair {
+        let x = &BigUint::random_below(rng, &(&self.q - 1)) + 1;
+        let y = self.g.modpow(&x, &self.p);
+        Keypair { x, y }
+    }
+
+    /// Sign a message hash with a given nonce. Fails if the nonce gives r = 0 or s = 0.
+    pub fn sign_with_nonce(&self, x: &BigUint, hash: &BigUint, k: &BigUint) -> Option<Signature> {
+        let r = &self.g.modpow(k, &self.p) % &self.q;
+        let k_inv = k.modinv(&self.q)?;
+        let s = &(&k_inv * &(hash + &(x * &r))) % &self.q;
+        if r.is_zero() || s.is_zero() {
+            None
+        } else {
+            Some(Signature { r, s })
+        }
+    }
+
+    pub fn sign<R: Rng>(&self, x: &BigUint, msg: &[u8], rng: &mut R) -> Signature {
+        let hash = hash_message(msg);
+        loop {
+            let k = &BigUint::random_below(rng, &(&self.q - 1)) + 1;
+            if let Some(signature) = self.sign_with_nonce(x, &hash, &k) {
+                return signature;
+            }
+        }
+    }
+
+    pub fn verify(&self, y: &BigUint, msg: &[u8], signature: &Signature) -> bool {
+        let Signature { r, s } = signature;
+        if r.is_zero() || r >= &self.q || s.is_zero() || s >= &self.q {
+            return false;
+        }
+        let w = match s.modinv(&self.q) {
+            Some(w) => w,
+            None => return false,
+        };
+        let u1 = &(&hash_message(msg) * &w) % &self.q;
+        let u2 = &(r * &w) % &self.q;
+        let v = &(&(&self.g.modpow(&u1, &self.p) * &y.modpow(&u2, &self.p)) % &self.p) % &self.q;
+        v == *r
+    }
+
+    /// x = (s * k - H(m)) / r mod q
+    pub fn private_key_from_nonce(
+        &self,
+        hash: &BigUint,
+        signature: &Signature,
+        k: &BigUint,
+    ) -> Option<BigUint> {
+        let q = &self.q;
+        let r_inv = signature.r.modinv(q)?;
+        let sk = &(&signature.s * k) % q;
+        Some(&(&sub_mod(&sk, &(hash % q), q) * &r_inv) % q)
+    }
+
+    /// Try every nonce in a range, returning the nonce and the private key matching `y`.
+    pub fn brute_force_nonce(
+        &self,
+        y: &BigUint,
+        hash: &BigUint,
+        signature: &Signature,
+        range: Range<u64>,
+    ) -> Option<(u64, BigUint)> {
+        // step g^k along the range instead of doing a full exponentiation for every k
+        let mut gk = self.g.modpow(&BigUint::from(range.start), &self.p);
+        for k in range {
+            if (&gk % &self.q) == signature.r {
+                let big_k = BigUint::from(k);
+                if let Some(x) = self.private_key_from_nonce(hash, signature, &big_k) {
+                    if self.g.modpow(&x, &self.p) == *y {
+                        return Some((k, x));
+                    }
+                }
+            }
+            gk = &(&gk * &self.g) % &self.p;
+        }
+        None
+    }
+
+    /// Two signatures made with the same nonce give it away:
+    /// k = (m1 - m2) / (s1 - s2) mod q
+    pub fn recover_repeated_nonce(&self, a: &SignedMessage, b: &SignedMessage) -> Option<BigUint> {
+        let q = &self.q;
+        let dm = sub_mod(&(&a.m % q), &(&b.m % q), q);
+        let ds = sub_mod(&a.signature.s, &b.signature.s, q);
+        let k = &(&dm * &ds.modinv(q)?) % q;
+        self.private_key_from_nonce(&a.m, &a.signature, &k)
+    }
+
+    /// Find two messages in a batch signed with the same nonce (they share r), and recover
+    /// the private key from them.
+    pub fn find_repeated_nonce(&self, messages: &[SignedMessage]) -> Option<BigUint> {
+        for (i, a) in messages.iter().enumerate() {
+            for b in messages[i + 1..].iter() {
+                if a.signature.r == b.signature.r && a.signature.s != b.signature.s {
+                    return self.recover_repeated_nonce(a, b);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The SHA-1 hash of a message as an integer.
+pub fn hash_message(msg: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(&sha1::digest(msg))
+}
+
+/// SHA-1 of the private key's hex representation, which is how Cryptopals checks answers.
+pub fn fingerprint(x: &BigUint) -> String {
+    hex::encode(&sha1::digest(x.to_hex().as_bytes()))
+}
+
+/// Parse signed messages in the challenge 44 format: groups of four lines
+/// `msg: <text>`, `s: <decimal>`, `r: <decimal>`, `m: <hex>`.
+pub fn parse_signed_messages(reader: impl BufRead) -> Result<Vec<SignedMessage>, String> {
+    let lines = reader
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let lines: Vec<&String> = lines.iter().filter(|l| !l.trim().is_empty()).collect();
+    if !lines.len().is_multiple_of(4) {
+        return Err("dsa: expected groups of four lines".to_string());
+    }
+
+    let field = |line: &str, name: &str| -> Result<String, String> {
+        line.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(": "))
+            .map(|v| v.to_string())
+            .ok_or_else(|| format!("dsa: expected {} line, got {:?}", name, line))
+    };
+
+    lines
+        .chunks(4)
+        .map(|group| {
+            let msg = field(group[0], "msg")?;
+            let s = field(group[1], "s")?.trim().parse()?;
+            let r = field(group[2], "r")?.trim().parse()?;
+            let m = BigUint::from_hex(field(group[3], "m")?.trim())?;
+            Ok(SignedMessage {
+                msg,
+                signature: Signature { r, s },
+                m,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint, hash_message, parse_signed_messages, Params, Signature};
+    use crate::bigint::BigUint;
+    use crate::rand::{SeedableRng, Xoshiro256};
+
+    #[test]
+    fn test_sign_verify() {
+        let mut rng = Xoshiro256::seed_from_u64(43);
+        let params = Params::cryptopals();
+        let key = params.generate_keypair(&mut rng);
+        let sig = params.sign(&key.x, b"hi mom", &mut rng);
+        assert!(params.verify(&key.y, b"hi mom", &sig));
+        assert!(!params.verify(&key.y, b"hi dad", &sig));
+    }
+
+    #[test]
+    fn test_brute_force_nonce() {
+        // Cryptopals challenge 43
+        let params = Params::cryptopals();
+        let y = BigUint::from_hex(
+            "84ad4719d044495496a3201c8ff484feb45b962e7302e56a392aee4abab3e4bd\
+             ebf2955b4736012f21a08084056b19bcd7fee56048e004e44984e2f411788efd\
+             c837a0d2e5abb7b555039fd243ac01f0fb2ed1dec568280ce678e931868d23eb\
+             095fde9d3779191b8c0299d6e07bbb283e6633451e535c45513b2d33c99ea17",
+        )
+        .unwrap();
+        let msg = b"For those that envy a MC it can be hazardous to your health\n\
+                    So be friendly, a matter of life and death, just like a etch-a-sketch\n";
+        let hash = hash_message(msg);
+        assert_eq!(hash.to_hex(), "d2d0714f014a9784047eaeccf956520045c45265");
+        let sig = Signature {
+            r: "548099063082341131477253921760299949438196259240"
+                .parse()
+                .unwrap(),
+            s: "857042759984254168557880549501802188789837994940"
+                .parse()
+                .unwrap(),
+        };
+        assert!(params.verify(&y, msg, &sig));
+
+        let (k, x) = params
+            .brute_force_nonce(&y, &hash, &sig, 0..1 << 16)
+            .unwrap();
+        assert_eq!(k, 16575);
+        assert_eq!(fingerprint(&x), "0954edd5e0afe5542a4adf012611a91912a3ec16");
+    }
+
+    #[test]
+    fn test_repeated_nonce() {
+        let mut rng = Xoshiro256::seed_from_u64(44);
+        let params = Params::cryptopals();
+        let key = params.generate_keypair(&mut rng);
+        let reused = BigUint::from(0xc0ffeeu64);
+
+        // write out a batch in the challenge 44 format, two of them sharing a nonce
+        let mut text = String::new();
+        let msgs = [
+            "Listen for me, you better listen for me now. ",
+            "Pure black people mon is all I mon know. ",
+            "Yeah me shoes a an tear up an' now me toes is a show a ",
+        ];
+        for (i, msg) in msgs.iter().enumerate() {
+            let hash = hash_message(msg.as_bytes());
+            let sig = if i == 0 {
+                params.sign(&key.x, msg.as_bytes(), &mut rng)
+            } else {
+                params.sign_with_nonce(&key.x, &hash, &reused).unwrap()
+            };
+            text.push_str(&format!(
+                "msg: {}\ns: {}\nr: {}\nm: {}\n",
+                msg,
+                sig.s,
+                sig.r,
+                hash.to_hex()
+            ));
+        }
+
+        let messages = parse_signed_messages(text.as_bytes()).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].msg, msgs[0]);
+        let x = params.find_repeated_nonce(&messages).unwrap();
+        assert_eq!(fingerprint(&x), fingerprint(&key.x));
+
+        assert!(parse_signed_messages("msg: foo\ns: 1\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Recovering the keystream of CTR ciphertexts encrypted under a reused nonce (Cryptopals
+//! challenges 19 and 20). Every ciphertext is xored with the same keystream, so byte i of
+//! each one is single-byte xor under keystream byte i.
+use crate::rank_single_byte_xor;
+use crate::repeating_xor;
+use crate::score::Scorer;
+use crate::xor::xor_bytes;
+
+pub struct Recovery {
+    ciphertexts: Vec<Vec<u8>>,
+    /// The keystream as far as the longest ciphertext.
+    pub keystream: Vec<u8>,
+}
+
+impl Recovery {
+    /// Guess the keystream statistically. Truncated to the shortest ciphertext, the set is
+    /// repeating-key xor with the key as long as that; past it, each column is scored on
+    /// whichever ciphertexts are still long enough.
+    pub fn new<S: Scorer + ?Sized>(ciphertexts: Vec<Vec<u8>>, scorer: &S) -> Self {
+        let common = ciphertexts.iter().map(|c| c.len()).min().unwrap_or(0);
+        let longest = ciphertexts.iter().map(|c| c.len()).max().unwrap_or(0);
+
+        let mut keystream = if common > 0 {
+            let truncated: Vec<u8> = ciphertexts
+                .iter()
+                .flat_map(|c| c[..common].iter().cloned())
+                .collect();
+            repeating_xor::find_key(&truncated, common, scorer)
+        } else {
+            vec![]
+        };
+        for i in common..longest {
+            let column: Vec<u8> = ciphertexts
+                .iter()
+                .filter_map(|c| c.get(i))
+                .cloned()
+                .collect();
+            keystream.push(rank_single_byte_xor(&column, scorer, 1)[0].key);
+        }
+
+        Recovery {
+            ciphertexts,
+            keystream,
+        }
+    }
+
+    pub fn plaintexts(&self) -> Vec<Vec<u8>> {
+        self.ciphertexts
+            .iter()
+            .map(|c| xor_bytes(c, &self.keystream[..c.len()]))
+            .collect()
+    }
+
+    /// Declare that ciphertext `index` decrypts to `crib` at `offset`, fixing the keystream
+    /// under it.
+    pub fn apply_crib(&mut self, index: usize, offset: usize, crib: &[u8]) -> Result<(), String> {
+        let ct = self
+            .ciphertexts
+            .get(index)
+            .ok_or_else(|| format!("fixed_nonce: no ciphertext {}", index))?;
+        let span = ct
+            .get(offset..offset + crib.len())
+            .ok_or_else(|| "fixed_nonce: crib runs past the ciphertext".to_string())?;
+        let keystream = xor_bytes(span, crib);
+        self.keystream[offset..offset + crib.len()].copy_from_slice(&keystream);
+        Ok(())
+    }
+
+    /// Drag each crib across every position of every ciphertext, and keep the placement
+    /// whose implied keystream makes all the ciphertexts score best over that span, if it
+    /// beats the current guess. Returns how many cribs were applied.
+    pub fn refine<S: Scorer + ?Sized>(&mut self, cribs: &[&[u8]], scorer: &S) -> usize {
+        let mut applied = 0;
+        for crib in cribs {
+            let mut best: Option<(f64, usize, usize)> = None;
+            for (index, ct) in self.ciphertexts.iter().enumerate() {
+                for offset in 0..(ct.len() + 1).saturating_sub(crib.len()) {
+                    let keystream = xor_bytes(&ct[offset..offset + crib.len()], crib);
+                    let score = self.span_score(offset, &keystream, scorer);
+                    if best.is_none_or(|(s, _, _)| score > s) {
+                        best = Some((score, index, offset));
+                    }
+                }
+            }
+            if let Some((score, index, offset)) = best {
+                let current = &self.keystream[offset..offset + crib.len()];
+                if score > self.span_score(offset, current, scorer) {
+                    self.apply_crib(index, offset, crib).unwrap();
+                    applied += 1;
+                }
+            }
+        }
+        applied
+    }
+
+    /// How good every ciphertext looks over `offset..offset + keystream.len()` when
+    /// decrypted with `keystream` there.
+    fn span_score<S: Scorer + ?Sized>(&self, offset: usize, keystream: &[u8], scorer: &S) -> f64 {
+        let decrypted: Vec<u8> = self
+            .ciphertexts
+            .iter()
+            .flat_map(|ct| {
+                let end = ct.len().min(offset + keystream.len());
+                let span = ct.get(offset..end).unwrap_or(&[]);
+                span.iter().zip(keystream).map(|(c, k)| c ^ k)
+            })
+            .collect();
+        scorer.score(&decrypted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recovery;
+    use crate::aes;
+    use crate::rand::{Rng, SeedableRng, Xoshiro256};
+    use crate::score::NgramModel;
+    use crate::xor::xor_bytes;
+
+    const LINES: [&str; 24] = [
+        "It was a bright cold day in April, and the clocks were striking.",
+        "The sky above the port was the color of television.",
+        "All happy families are alike in their own quiet way.",
+        "Far out in the uncharted backwaters of the western spiral arm",
+        "In a hole in the ground there lived a hobbit, and he was content.",
+        "The man in black fled across the desert, and the gunslinger followed.",
+        "It is a truth universally acknowledged by all who have tried it",
+        "Mother died today, or maybe yesterday, I cannot be sure of it.",
+        "You don't know about me without you have read a book by that name",
+        "Someone must have been telling lies about the man they arrested.",
+        "We were somewhere around the edge of the desert when it began.",
+        "Whether I shall turn out to be the hero of my own life or not",
+        "Happy families are all alike; every unhappy one is different.",
+        "Call me by the name my father gave me, and I will answer you.",
+        "The past is a foreign country; they do things differently there.",
+        "It was the best of times, it was the worst of times, they said",
+        "I am an invisible man, no, not a spook like those who haunted",
+        "Many years later, as he faced the firing squad, he remembered",
+        "The snow in the mountains was melting and the river ran high",
+        "There was no possibility of taking a walk that day in the rain.",
+        "Once upon a time there was a little girl who lived in a forest",
+        "The cold passed reluctantly from the earth, and retiring fogs",
+        "As the morning came over the hills the town began to stir again",
+        "The story so far: in the beginning the universe was created.",
+    ];
+
+    fn encrypt_all() -> Vec<Vec<u8>> {
+        let key = Xoshiro256::seed_from_u64(19).get_bytes(aes::KEY_SIZE as u64);
+        LINES
+            .iter()
+            .map(|line| aes::encrypt_ctr(line.as_bytes(), &key, &[0u8; 8]))
+            .collect()
+    }
+
+    #[test]
+    fn test_statistical_recovery() {
+        let scorer = NgramModel::english_unigrams();
+        let recovery = Recovery::new(encrypt_all(), &scorer);
+        let longest = LINES.iter().map(|l| l.len()).max().unwrap();
+        assert_eq!(recovery.keystream.len(), longest);
+
+        // the part every line covers has plenty of samples per column
+        let common = LINES.iter().map(|l| l.len()).min().unwrap();
+        let plaintexts = recovery.plaintexts();
+        let right = plaintexts
+            .iter()
+            .zip(LINES.iter())
+            .map(|(pt, line)| (0..common).filter(|&i| pt[i] == line.as_bytes()[i]).count())
+            .sum::<usize>();
+        assert!(right * 10 >= common * LINES.len() * 9);
+    }
+
+    #[test]
+    fn test_cribs() {
+        let scorer = NgramModel::english_unigrams();
+        let ciphertexts = encrypt_all();
+        let mut recovery = Recovery::new(ciphertexts.clone(), &scorer);
+        let longest = (0..LINES.len()).max_by_key(|&i| LINES[i].len()).unwrap();
+        let truth = xor_bytes(&ciphertexts[longest], LINES[longest].as_bytes());
+
+        // an applied crib only rewrites its own span, and only to score better there
+        recovery.keystream[30..45].copy_from_slice(&[0u8; 15]);
+        let known: &[u8] = &LINES[0].as_bytes()[28..42];
+        let cribs: [&[u8]; 4] = [b" the ", b"there", known, b"again"];
+        let mut total = 0;
+        for &crib in cribs.iter() {
+            let before = recovery.keystream.clone();
+            let applied = recovery.refine(&[crib], &scorer);
+            let after = recovery.keystream.clone();
+            if applied == 0 {
+                assert_eq!(after, before);
+                continue;
+            }
+            assert_eq!(applied, 1);
+            total += applied;
+            let improved = (0..=after.len() - crib.len()).any(|offset| {
+                let span = offset..offset + crib.len();
+                (0..after.len()).all(|i| span.contains(&i) || after[i] == before[i])
+                    && recovery.span_score(offset, &after[span.clone()], &scorer)
+                        > recovery.span_score(offset, &before[span], &scorer)
+            });
+            assert!(improved, "{:?}", String::from_utf8_lossy(crib));
+        }
+        assert!(total > 0);
+
+        // the crib known to be right went where it belongs, and now nothing beats it
+        assert_eq!(known, b"April, and the");
+        assert_eq!(recovery.keystream[28..42], truth[28..42]);
+        assert_eq!(recovery.refine(&[known], &scorer), 0);
+
+        // knowing the longest line gives away the whole keystream
+        recovery
+            .apply_crib(longest, 0, LINES[longest].as_bytes())
+            .unwrap();
+        for (pt, line) in recovery.plaintexts().iter().zip(LINES.iter()) {
+            assert_eq!(pt, line.as_bytes());
+        }
+
+        assert!(recovery.apply_crib(0, 60, b"too long").is_err());
+        assert!(recovery.apply_crib(LINES.len(), 0, b"x").is_err());
+    }
+}
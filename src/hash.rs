@@ -0,0 +1,85 @@
+//! Common interface to the crate's hash functions.
+
+/// A streaming hash function.
+pub trait Hash: Sized {
+    /// Size in bytes of the blocks fed to the compression function.
+    const BLOCK_SIZE: usize;
+    /// Size in bytes of the digest.
+    const OUTPUT_SIZE: usize;
+
+    fn new() -> Self;
+    fn update(&mut self, bytes: &[u8]);
+    fn finalize(self) -> Vec<u8>;
+
+    /// Hash a message in one go.
+    fn digest(bytes: &[u8]) -> Vec<u8> {
+        let mut hash = Self::new();
+        hash.update(bytes);
+        hash.finalize()
+    }
+}
+
+/// The Merkle-Damgard padding SHA-1 and SHA-256 append to a message of `len` bytes:
+/// a 1 bit, zeros, and the message length in bits as a big-endian u64, filling out a
+/// 64-byte block.
+pub fn md_padding(len: u64) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+    let zeros = (BLOCK_SIZE - (len as usize + 9) % BLOCK_SIZE) % BLOCK_SIZE;
+    let mut padding = vec![0x80];
+    padding.resize(1 + zeros, 0);
+    padding.extend_from_slice(&(len.wrapping_mul(8)).to_be_bytes());
+    padding
+}
+
+/// The input side of a Merkle-Damgard hash: collects bytes into blocks and hands each full
+/// block to the compression function, counting everything it has seen.
+#[derive(Clone)]
+pub(crate) struct BlockBuffer {
+    block_size: usize,
+    // bytes processed so far, including any buffered ones
+    len: u64,
+    buffer: Vec<u8>,
+}
+
+impl BlockBuffer {
+    /// A buffer that has already seen `len` bytes, which must be whole blocks.
+    pub(crate) fn new(block_size: usize, len: u64) -> Self {
+        assert!(
+            len.is_multiple_of(block_size as u64),
+            "from_state: len must be a whole number of blocks"
+        );
+        BlockBuffer {
+            block_size,
+            len,
+            buffer: Vec::with_capacity(block_size),
+        }
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Is there no partial block waiting for more bytes?
+    pub(crate) fn is_aligned(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub(crate) fn update<F: FnMut(&[u8])>(&mut self, mut bytes: &[u8], mut compress: F) {
+        self.len += bytes.len() as u64;
+        if !self.buffer.is_empty() {
+            let n = (self.block_size - self.buffer.len()).min(bytes.len());
+            self.buffer.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.buffer.len() < self.block_size {
+                return;
+            }
+            compress(&self.buffer);
+            self.buffer.clear();
+        }
+        let mut blocks = bytes.chunks_exact(self.block_size);
+        for block in &mut blocks {
+            compress(block);
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+}
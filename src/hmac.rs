@@ -0,0 +1,122 @@
+//! HMAC (RFC 2104) over any of the crate's hash functions.
+use std::thread;
+use std::time::Duration;
+
+use crate::hash::Hash;
+
+/// How a received MAC is checked against the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// Look at every byte no matter where the first mismatch is.
+    ConstantTime,
+    /// Deliberately leaky: bail out at the first wrong byte, sleeping this long per
+    /// matching byte so the leak can be measured.
+    EarlyExit(Duration),
+}
+
+pub fn hmac<H: Hash>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut key = if key.len() > H::BLOCK_SIZE {
+        H::digest(key)
+    } else {
+        key.to_vec()
+    };
+    key.resize(H::BLOCK_SIZE, 0);
+
+    let ipad: Vec<u8> = key.iter().map(|k| k ^ 0x36).collect();
+    let opad: Vec<u8> = key.iter().map(|k| k ^ 0x5c).collect();
+
+    let mut inner = H::new();
+    inner.update(&ipad);
+    inner.update(message);
+    let inner = inner.finalize();
+
+    let mut outer = H::new();
+    outer.update(&opad);
+    outer.update(&inner);
+    outer.finalize()
+}
+
+/// Check a MAC for a message, comparing as requested.
+pub fn verify<H: Hash>(key: &[u8], message: &[u8], mac: &[u8], comparison: Comparison) -> bool {
+    let expected = hmac::<H>(key, message);
+    match comparison {
+        Comparison::ConstantTime => constant_time_eq(&expected, mac),
+        Comparison::EarlyExit(delay) => insecure_compare(&expected, mac, delay),
+    }
+}
+
+/// Compare two byte strings in time that depends only on their length.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+/// Compare byte by byte, sleeping after each match and returning at the first difference.
+pub fn insecure_compare(a: &[u8], b: &[u8], delay: Duration) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    for (x, y) in a.iter().zip(b.iter()) {
+        if x != y {
+            return false;
+        }
+        thread::sleep(delay);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, hmac, insecure_compare, verify, Comparison};
+    use crate::hex;
+    use crate::md4::Md4;
+    use crate::sha1::Sha1;
+    use std::time::Duration;
+
+    #[test]
+    fn test_hmac_sha1() {
+        // RFC 2202, test cases 1, 2 and 6
+        assert_eq!(
+            hex::encode(&hmac::<Sha1>(&[0x0b; 20], b"Hi There")),
+            "b617318655057264e28bc0b6fb378c8ef146be00"
+        );
+        assert_eq!(
+            hex::encode(&hmac::<Sha1>(b"Jefe", b"what do ya want for nothing?")),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            hex::encode(&hmac::<Sha1>(
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let key = b"YELLOW SUBMARINE";
+        let mac = hmac::<Md4>(key, b"foo");
+        assert_eq!(mac.len(), 16);
+
+        for &comparison in [
+            Comparison::ConstantTime,
+            Comparison::EarlyExit(Duration::ZERO),
+        ]
+        .iter()
+        {
+            assert!(verify::<Md4>(key, b"foo", &mac, comparison));
+            assert!(!verify::<Md4>(key, b"bar", &mac, comparison));
+            assert!(!verify::<Md4>(key, b"foo", &mac[1..], comparison));
+        }
+
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!insecure_compare(b"abc", b"xbc", Duration::ZERO));
+    }
+}
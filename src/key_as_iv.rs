@@ -0,0 +1,148 @@
+//! Recovering the key from CBC that uses it as the IV as well (Cryptopals challenge 27), given
+//! a receiver that complains about non-ASCII plaintext by echoing it back.
+use std::fmt;
+
+use crate::aes;
+use crate::pad::PaddingError;
+use crate::rand;
+use crate::xor::xor_bytes;
+
+/// Why the server rejected a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    Padding(PaddingError),
+    /// The plaintext has bytes above 0x7f. It comes back in the error, as a careless error
+    /// page would show it.
+    NotAscii(Vec<u8>),
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecryptError::Padding(e) => write!(f, "key_as_iv: {}", e),
+            DecryptError::NotAscii(pt) => write!(
+                f,
+                "key_as_iv: non-ASCII plaintext {:?}",
+                String::from_utf8_lossy(pt)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Stand-in for a service encrypting under AES-CBC with IV = key.
+pub struct Server {
+    key: Vec<u8>,
+}
+
+impl Server {
+    /// A server with a random key.
+    pub fn new() -> Self {
+        Server::with_key(&rand::bytes(aes::KEY_SIZE as u64))
+    }
+
+    pub fn with_key(key: &[u8]) -> Self {
+        Server { key: key.to_vec() }
+    }
+
+    pub fn encrypt(&self, pt: &[u8]) -> Vec<u8> {
+        aes::encrypt_cbc(pt, &self.key, &self.key)
+    }
+
+    pub fn decrypt(&self, ct: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let pt = aes::decrypt_cbc(ct, &self.key, &self.key).map_err(DecryptError::Padding)?;
+        if pt.is_ascii() {
+            Ok(pt)
+        } else {
+            Err(DecryptError::NotAscii(pt))
+        }
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+/// Recover the key from a ciphertext of at least two blocks and a `decrypt` oracle.
+///
+/// Sending C1, 0, C1 decrypts to P'1 = D(C1) ^ IV and P'3 = D(C1) ^ 0, so P'1 ^ P'3 is the
+/// IV, which is the key. The last two original blocks go on the end to keep the padding
+/// valid. The garbage plaintext is almost surely not ASCII, and the oracle's error hands it
+/// to us.
+pub fn recover_key<F>(ct: &[u8], decrypt: F) -> Result<Vec<u8>, String>
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>, DecryptError>,
+{
+    let bs = aes::KEY_SIZE;
+    if ct.len() < 2 * bs || !ct.len().is_multiple_of(bs) {
+        return Err("key_as_iv: need a ciphertext of two or more whole blocks".to_string());
+    }
+    let first = &ct[..bs];
+    let mut forged = first.to_vec();
+    forged.extend_from_slice(&[0u8; aes::KEY_SIZE]);
+    forged.extend_from_slice(first);
+    forged.extend_from_slice(&ct[ct.len() - 2 * bs..]);
+
+    match decrypt(&forged) {
+        Err(DecryptError::NotAscii(pt)) => Ok(xor_bytes(&pt[..bs], &pt[2 * bs..3 * bs])),
+        Err(e) => Err(e.to_string()),
+        Ok(_) => Err("key_as_iv: the forged message decrypted to ASCII".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recover_key, DecryptError, Server};
+    use crate::aes;
+
+    const MESSAGE: &[u8] =
+        b"comment1=cooking%20MCs;userdata=x;comment2=%20like%20a%20pound%20of%20bacon";
+
+    #[test]
+    fn test_server() {
+        let server = Server::new();
+        let ct = server.encrypt(MESSAGE);
+        assert_eq!(server.decrypt(&ct).unwrap(), MESSAGE);
+
+        let ct = server.encrypt("caf\u{e9}".as_bytes());
+        assert_eq!(
+            server.decrypt(&ct),
+            Err(DecryptError::NotAscii("caf\u{e9}".as_bytes().to_vec()))
+        );
+        assert!(matches!(
+            server.decrypt(&ct[1..]),
+            Err(DecryptError::Padding(_))
+        ));
+    }
+
+    #[test]
+    fn test_recover_key() {
+        let key = b"YELLOW SUBMARINE";
+        let server = Server::with_key(key);
+        // also works when the message is exactly two blocks
+        for &msg in [MESSAGE, &MESSAGE[..20]].iter() {
+            let ct = server.encrypt(msg);
+            assert_eq!(recover_key(&ct, |ct| server.decrypt(ct)).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_recovered_key_decrypts() {
+        let server = Server::new();
+        let key = recover_key(&server.encrypt(MESSAGE), |ct| server.decrypt(ct)).unwrap();
+        for msg in [&b""[..], b"attack at dawn", b";admin=true;", MESSAGE].iter() {
+            let ct = server.encrypt(msg);
+            assert_eq!(aes::decrypt_cbc(&ct, &key, &key).unwrap(), *msg);
+        }
+    }
+
+    #[test]
+    fn test_short_ciphertext() {
+        let server = Server::new();
+        let ct = server.encrypt(b"short");
+        assert!(recover_key(&ct, |ct| server.decrypt(ct)).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Encoding of ordered `key=value` fields, like `email=foo@bar.com&uid=10&role=user` or
+//! `comment1=cooking%20MCs;userdata=foo`, with a choice of how metacharacters in values are
+//! handled.
+use crate::hex;
+
+/// A decoded key and value.
+pub type Field = (Vec<u8>, Vec<u8>);
+
+/// What happens to separators and `%` in the values being encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escaping {
+    /// Pass them through, so a value can inject fields of its own.
+    None,
+    /// Drop them.
+    Strip,
+    /// Write them as `%XX`, and turn that back when decoding.
+    Percent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Codec {
+    pub pair_separator: u8,
+    pub kv_separator: u8,
+    pub escaping: Escaping,
+}
+
+impl Codec {
+    pub fn new(pair_separator: u8, kv_separator: u8, escaping: Escaping) -> Self {
+        Codec {
+            pair_separator,
+            kv_separator,
+            escaping,
+        }
+    }
+
+    /// `a=b&c=d`
+    pub fn query_string(escaping: Escaping) -> Self {
+        Codec::new(b'&', b'=', escaping)
+    }
+
+    /// `a=b;c=d`
+    pub fn cookie(escaping: Escaping) -> Self {
+        Codec::new(b';', b'=', escaping)
+    }
+
+    fn is_meta(&self, b: u8) -> bool {
+        b == self.pair_separator || b == self.kv_separator || b == b'%'
+    }
+
+    pub fn escape(&self, value: &[u8]) -> Vec<u8> {
+        match self.escaping {
+            Escaping::None => value.to_vec(),
+            Escaping::Strip => value
+                .iter()
+                .filter(|&&b| !self.is_meta(b))
+                .cloned()
+                .collect(),
+            Escaping::Percent => {
+                let mut out = Vec::with_capacity(value.len());
+                for &b in value {
+                    if self.is_meta(b) {
+                        out.push(b'%');
+                        out.extend_from_slice(hex::encode(&[b]).to_uppercase().as_bytes());
+                    } else {
+                        out.push(b);
+                    }
+                }
+                out
+            }
+        }
+    }
+
+    pub fn unescape(&self, value: &[u8]) -> Result<Vec<u8>, String> {
+        if self.escaping != Escaping::Percent {
+            return Ok(value.to_vec());
+        }
+        let mut out = Vec::with_capacity(value.len());
+        let mut i = 0;
+        while i < value.len() {
+            if value[i] == b'%' {
+                let code = value
+                    .get(i + 1..i + 3)
+                    .and_then(|h| hex::parse(String::from_utf8_lossy(h).to_lowercase()).ok())
+                    .ok_or_else(|| "kv: bad escape".to_string())?;
+                out.extend_from_slice(&code);
+                i += 3;
+            } else {
+                out.push(value[i]);
+                i += 1;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Join fields, escaping keys and values.
+    pub fn encode<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, fields: &[(K, V)]) -> Vec<u8> {
+        let mut out = vec![];
+        for (i, (k, v)) in fields.iter().enumerate() {
+            if i > 0 {
+                out.push(self.pair_separator);
+            }
+            out.extend_from_slice(&self.escape(k.as_ref()));
+            out.push(self.kv_separator);
+            out.extend_from_slice(&self.escape(v.as_ref()));
+        }
+        out
+    }
+
+    /// Split into fields, in order. Every pair must have a key separator; only the first
+    /// one counts, so values may contain more. Duplicate keys are all kept.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<Field>, String> {
+        if bytes.is_empty() {
+            return Ok(vec![]);
+        }
+        bytes
+            .split(|&b| b == self.pair_separator)
+            .map(|pair| {
+                let at = pair
+                    .iter()
+                    .position(|&b| b == self.kv_separator)
+                    .ok_or_else(|| {
+                        format!("kv: no value in {:?}", String::from_utf8_lossy(pair))
+                    })?;
+                Ok((self.unescape(&pair[..at])?, self.unescape(&pair[at + 1..])?))
+            })
+            .collect()
+    }
+}
+
+/// The value of the first field called `key`.
+pub fn get<'a>(fields: &'a [Field], key: &str) -> Option<&'a [u8]> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key.as_bytes())
+        .map(|(_, v)| &v[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get, Codec, Escaping};
+
+    #[test]
+    fn test_round_trip() {
+        let fields = [("foo", "bar"), ("baz", "qux"), ("zap", "zazzle")];
+        for &escaping in [Escaping::None, Escaping::Strip, Escaping::Percent].iter() {
+            let codec = Codec::query_string(escaping);
+            let encoded = codec.encode(&fields);
+            assert_eq!(encoded, b"foo=bar&baz=qux&zap=zazzle");
+            let decoded = codec.decode(&encoded).unwrap();
+            assert_eq!(decoded.len(), 3);
+            assert_eq!(get(&decoded, "zap"), Some(&b"zazzle"[..]));
+            assert_eq!(get(&decoded, "nope"), None);
+        }
+        assert_eq!(
+            Codec::cookie(Escaping::None).encode(&[("a", "b"), ("c", "d")]),
+            b"a=b;c=d"
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        let evil = [("email", "foo@bar.com&role=admin")];
+
+        let codec = Codec::query_string(Escaping::None);
+        let decoded = codec.decode(&codec.encode(&evil)).unwrap();
+        assert_eq!(get(&decoded, "role"), Some(&b"admin"[..]));
+
+        let codec = Codec::query_string(Escaping::Strip);
+        assert_eq!(codec.encode(&evil), b"email=foo@bar.comroleadmin");
+
+        let codec = Codec::cookie(Escaping::Percent);
+        let encoded = codec.encode(&[("userdata", ";admin=true;100%")]);
+        assert_eq!(encoded, b"userdata=%3Badmin%3Dtrue%3B100%25");
+        let decoded = codec.decode(&encoded).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(get(&decoded, "userdata"), Some(&b";admin=true;100%"[..]));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let codec = Codec::query_string(Escaping::Percent);
+        assert!(codec.decode(b"foo=bar&baz").is_err());
+        assert!(codec.decode(b"foo=%4").is_err());
+        assert!(codec.decode(b"foo=%zz").is_err());
+        assert_eq!(codec.decode(b"").unwrap(), vec![]);
+        assert_eq!(
+            codec.decode(b"a=b=c").unwrap(),
+            vec![(b"a".to_vec(), b"b=c".to_vec())]
+        );
+    }
+}
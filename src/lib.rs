@@ -1,89 +1,173 @@
-use std::iter;
+use std::cmp::Ordering;
+
+use score::{NgramModel, Scorer};
 
 pub mod aes;
 pub mod b64;
+pub mod bigint;
+pub mod bitflip;
+pub mod byte_at_a_time;
+pub mod cut_paste;
+pub mod dh;
+pub mod dsa;
+pub mod fixed_nonce;
+pub mod hash;
 pub mod hex;
+pub mod hmac;
+pub mod key_as_iv;
+pub mod kv;
+pub mod md4;
+pub mod mode;
 pub mod pad;
+pub mod padding_oracle;
 pub mod rand;
+pub mod random_access;
+pub mod repeating_xor;
+pub mod rsa;
+pub mod score;
+pub mod seed_recovery;
+pub mod sha1;
+pub mod sha256;
+pub mod srp;
 pub mod xor;
 
-mod ascii {
-    const ASCII: &str = "abcdefghijklmnopqrstuvwxyz ";
-
-    pub fn ix(c: char) -> usize {
-        assert!(ASCII.contains(c.to_ascii_lowercase()));
-        ASCII.find(c.to_ascii_lowercase()).unwrap()
-    }
+// (score, key, text)
+pub fn break_single_byte_xor(bytes: &[u8]) -> Result<(f64, u8, String), String> {
+    break_single_byte_xor_with(bytes, &NgramModel::english_unigrams())
 }
 
-/// Count the number of 1-valued bits in a byte slice.
-fn bit_count(bytes: &[u8]) -> u32 {
-    bytes
-        .iter()
-        .map(|&x| (0..=7).map(|i| (x as u32 & 2_u32.pow(i)) >> i).sum::<u32>())
-        .sum()
+/// Like `break_single_byte_xor`, ranking the candidates with the given scorer.
+pub fn break_single_byte_xor_with<S: Scorer + ?Sized>(
+    bytes: &[u8],
+    scorer: &S,
+) -> Result<(f64, u8, String), String> {
+    rank_single_byte_xor(bytes, scorer, 256)
+        .into_iter()
+        .find_map(|c| {
+            let text = String::from_utf8(c.plaintext).ok()?;
+            Some((c.score, c.key, text))
+        })
+        .ok_or_else(|| "no valid utf8 strings found".to_string())
 }
 
-pub fn english_score(text: &str) -> f32 {
-    let english_freq = [
-        0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, // A-G
-        0.06094, 0.06966, 0.00153, 0.00772, 0.04025, 0.02406, 0.06749, // H-N
-        0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056, 0.02758, // O-U
-        0.00978, 0.02360, 0.00150, 0.01974, 0.00074, 0.13000, // V-Z, ' '
-    ];
-    text.chars()
-        .filter(|&c| c.is_ascii_alphabetic() || c == ' ')
-        .map(|c| english_freq[ascii::ix(c)])
-        .sum()
+/// One guess at a single-byte xor key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub key: u8,
+    pub score: f64,
+    pub plaintext: Vec<u8>,
 }
 
-// (score, key, text)
-pub fn break_single_byte_xor(bytes: &[u8]) -> Result<(f32, u8, String), String> {
-    let mut scores = vec![];
-
-    for k in 0..=255u8 {
-        let key: Vec<u8> = iter::repeat(k).take(bytes.len()).collect();
-        match String::from_utf8(xor::xor_bytes(bytes, &key)) {
-            Ok(text) => scores.push((english_score(&text), k, text)),
-            _ => continue,
+impl Candidate {
+    /// Higher scores first, then lower keys, with NaN scores last.
+    pub fn rank(a: &Candidate, b: &Candidate) -> Ordering {
+        match (a.score.is_nan(), b.score.is_nan()) {
+            (false, true) => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            _ => b.score.total_cmp(&a.score).then(a.key.cmp(&b.key)),
         }
     }
-
-    scores.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-    if !scores.is_empty() {
-        Ok(scores[0].clone())
-    } else {
-        Err("no valid utf8 strings found".to_string())
-    }
 }
 
-pub fn find_xor_key_size(bytes: &[u8]) -> usize {
-    let mut mindist = f32::MAX;
-    let mut keysize: usize = 0;
-    for candidate in 2..=40 {
-        let chunks: Vec<_> = bytes.chunks(candidate).take(4).collect();
-        let mut dist = 0f32;
-        for i in 0..4 {
-            for j in 0..4 {
-                dist += hamming(chunks[i], chunks[j]) as f32;
+/// Try every single-byte key and return the `top` best candidates, best first.
+pub fn rank_single_byte_xor<S: Scorer + ?Sized>(
+    bytes: &[u8],
+    scorer: &S,
+    top: usize,
+) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = (0..=255u8)
+        .map(|key| {
+            let plaintext: Vec<u8> = xor::xor_repeating(bytes, &[key]).collect();
+            Candidate {
+                key,
+                score: scorer.score(&plaintext),
+                plaintext,
             }
-        }
-        dist /= candidate as f32;
-        if dist < mindist {
-            keysize = candidate;
-            mindist = dist;
-        }
-    }
-    keysize
+        })
+        .collect();
+    candidates.sort_by(Candidate::rank);
+    candidates.truncate(top);
+    candidates
 }
 
-pub fn build_repeated_key(b: &[u8], len: usize) -> Vec<u8> {
-    iter::repeat(b).flatten().cloned().take(len).collect()
+/// The most likely repeating-xor key size between 2 and 40, or `None` if the input is too
+/// short to tell.
+pub fn find_xor_key_size(bytes: &[u8]) -> Option<usize> {
+    repeating_xor::rank_key_sizes(bytes, repeating_xor::KEY_SIZES)
+        .first()
+        .map(|k| k.size)
 }
 
+/// Number of differing bits between two equally long byte slices.
 pub fn hamming(b0: &[u8], b1: &[u8]) -> u32 {
-    bit_count(&xor::xor_bytes(b0, b1))
+    assert_eq!(b0.len(), b1.len(), "hamming: length mismatch");
+    b0.iter().zip(b1).map(|(a, b)| (a ^ b).count_ones()).sum()
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::{break_single_byte_xor, rank_single_byte_xor, Candidate};
+    use crate::hex;
+    use crate::score::{NgramModel, Scorer};
+
+    /// Scores everything the same, except NaN for key 0x00's output.
+    struct Flat;
+
+    impl Scorer for Flat {
+        fn score(&self, bytes: &[u8]) -> f64 {
+            if bytes == b"\x01\x02" {
+                f64::NAN
+            } else {
+                1.0
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank_single_byte_xor() {
+        let bytes =
+            hex::parse("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736")
+                .unwrap();
+        let ranked = rank_single_byte_xor(&bytes, &NgramModel::english_unigrams(), 5);
+        assert_eq!(ranked.len(), 5);
+        assert_eq!(ranked[0].key, b'X');
+        assert_eq!(ranked[0].plaintext, b"Cooking MC's like a pound of bacon");
+        assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let (_, key, text) = break_single_byte_xor(&bytes).unwrap();
+        assert_eq!(
+            (key, text.as_str()),
+            (b'X', "Cooking MC's like a pound of bacon")
+        );
+    }
+
+    #[test]
+    fn test_rank_binary_and_nan() {
+        // non-UTF-8 output is still ranked
+        let bytes: Vec<u8> = [0xff, 0xfe, 0x80].iter().map(|b| b ^ 0x42).collect();
+        let ranked = rank_single_byte_xor(&bytes, &Flat, 256);
+        assert_eq!(ranked.len(), 256);
+        assert!(ranked.iter().any(|c| c.plaintext == [0xff, 0xfe, 0x80]));
+
+        // ties go by key and NaN sinks to the bottom
+        let ranked = rank_single_byte_xor(&[0x01, 0x02], &Flat, 256);
+        assert_eq!(ranked[0].key, 0x01);
+        assert_eq!(ranked[255].key, 0x00);
+        assert!(ranked[255].score.is_nan());
+
+        let mut candidates = [
+            Candidate {
+                key: 1,
+                score: f64::NAN,
+                plaintext: vec![],
+            },
+            Candidate {
+                key: 2,
+                score: -1.0,
+                plaintext: vec![],
+            },
+        ];
+        candidates.sort_by(Candidate::rank);
+        assert_eq!(candidates[0].key, 2);
+    }
+}
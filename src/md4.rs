@@ -0,0 +1,197 @@
+//! MD4 (RFC 1320), with its internal state exposed so it can be resumed for
+//! length-extension attacks.
+use crate::hash::{BlockBuffer, Hash};
+
+pub const BLOCK_SIZE: usize = 64;
+pub const OUTPUT_SIZE: usize = 16;
+
+const INITIAL_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+const ROUND2_ORDER: [usize; 16] = [0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15];
+const ROUND3_ORDER: [usize; 16] = [0, 8, 4, 12, 2, 10, 6, 14, 1, 9, 5, 13, 3, 11, 7, 15];
+
+#[derive(Clone)]
+pub struct Md4 {
+    state: [u32; 4],
+    buffer: BlockBuffer,
+}
+
+impl Md4 {
+    pub fn new() -> Self {
+        Md4::from_state(INITIAL_STATE, 0)
+    }
+
+    /// Resume hashing from a digest state after `len` bytes (message plus padding) were processed.
+    pub fn from_state(state: [u32; 4], len: u64) -> Self {
+        Md4 {
+            state,
+            buffer: BlockBuffer::new(BLOCK_SIZE, len),
+        }
+    }
+
+    pub fn state(&self) -> [u32; 4] {
+        self.state
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.update(bytes, |block| compress(state, block));
+    }
+
+    pub fn finalize(mut self) -> [u8; OUTPUT_SIZE] {
+        let padding = md_padding(self.buffer.len());
+        self.update(&padding);
+        debug_assert!(self.buffer.is_aligned());
+
+        let mut digest = [0u8; OUTPUT_SIZE];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+/// Run the compression function over one block.
+fn compress(state: &mut [u32; 4], block: &[u8]) {
+    let mut x = [0u32; 16];
+    for (i, word) in block.chunks(4).enumerate() {
+        x[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    // the registers rotate through a, d, c, b; h[t] is the one updated at each step
+    let mut h = *state;
+    for i in 0..48 {
+        let t = (4 - i % 4) % 4;
+        let (b, c, d) = (h[(t + 1) % 4], h[(t + 2) % 4], h[(t + 3) % 4]);
+        let (f, k, s) = match i / 16 {
+            0 => ((b & c) | (!b & d), i, [3, 7, 11, 19][i % 4]),
+            1 => (
+                ((b & c) | (b & d) | (c & d)).wrapping_add(0x5a827999),
+                ROUND2_ORDER[i % 16],
+                [3, 5, 9, 13][i % 4],
+            ),
+            _ => (
+                (b ^ c ^ d).wrapping_add(0x6ed9eba1),
+                ROUND3_ORDER[i % 16],
+                [3, 9, 11, 15][i % 4],
+            ),
+        };
+        h[t] = h[t].wrapping_add(f).wrapping_add(x[k]).rotate_left(s);
+    }
+
+    for (s, x) in state.iter_mut().zip(h.iter()) {
+        *s = s.wrapping_add(*x);
+    }
+}
+
+impl Default for Md4 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hash for Md4 {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    const OUTPUT_SIZE: usize = OUTPUT_SIZE;
+
+    fn new() -> Self {
+        Md4::new()
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        Md4::update(self, bytes)
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        Md4::finalize(self).to_vec()
+    }
+}
+
+/// Hash a message in one go.
+pub fn digest(bytes: &[u8]) -> [u8; OUTPUT_SIZE] {
+    let mut md4 = Md4::new();
+    md4.update(bytes);
+    md4.finalize()
+}
+
+/// The padding MD4 appends to a message of `len` bytes: a 1 bit, zeros, and the message
+/// length in bits as a little-endian u64.
+pub fn md_padding(len: u64) -> Vec<u8> {
+    let zeros = (BLOCK_SIZE - (len as usize + 9) % BLOCK_SIZE) % BLOCK_SIZE;
+    let mut padding = vec![0x80];
+    padding.resize(1 + zeros, 0);
+    padding.extend_from_slice(&(len.wrapping_mul(8)).to_le_bytes());
+    padding
+}
+
+/// Split a digest back into the four registers it was made from.
+pub fn state_from_digest(digest: &[u8]) -> [u32; 4] {
+    assert_eq!(digest.len(), OUTPUT_SIZE);
+    let mut state = [0u32; 4];
+    for (word, chunk) in state.iter_mut().zip(digest.chunks(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest, md_padding, state_from_digest, Md4};
+    use crate::hex;
+
+    #[test]
+    fn test_md4() {
+        // RFC 1320, appendix A.5
+        let vectors = [
+            ("", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            ("a", "bde52cb31de33e46245e05fbdbd6fb24"),
+            ("abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            ("message digest", "d9130a8164549fe818874806e1c7014b"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "d79e1c308aa5bbcdeea8ed63df412da9",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "043f8582f241db351ce627e153e7f0e4",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+            ),
+        ];
+        for (msg, hash) in vectors.iter() {
+            assert_eq!(hex::encode(&digest(msg.as_bytes())), *hash);
+
+            let mut md4 = Md4::new();
+            for b in msg.as_bytes().chunks(7) {
+                md4.update(b);
+            }
+            assert_eq!(hex::encode(&md4.finalize()), *hash);
+        }
+    }
+
+    #[test]
+    fn test_length_extension() {
+        let key = b"YELLOW SUBMARINE";
+        let msg = b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+        let extension = b";admin=true";
+
+        let secret: Vec<u8> = key.iter().chain(msg.iter()).cloned().collect();
+        let mac = digest(&secret);
+
+        let glue = md_padding(secret.len() as u64);
+        let processed = (secret.len() + glue.len()) as u64;
+        let mut forger = Md4::from_state(state_from_digest(&mac), processed);
+        forger.update(extension);
+        let forged = forger.finalize();
+
+        let extended: Vec<u8> = secret
+            .iter()
+            .chain(glue.iter())
+            .chain(extension.iter())
+            .cloned()
+            .collect();
+        assert_eq!(forged, digest(&extended));
+    }
+}
@@ -0,0 +1,476 @@
+//! Block cipher modes of operation, generic over any cipher implementing the `cipher` crate's
+//! `BlockEncrypt`/`BlockDecrypt`, so the same code runs against AES-128/192/256 or a toy cipher.
+use aes::cipher::{generic_array::GenericArray, Block, BlockDecrypt, BlockEncrypt, BlockSizeUser};
+
+use crate::pad::{self, PaddingError};
+use crate::xor::{xor_bytes, xor_in_place};
+
+/// A mode of operation bound to a cipher and its parameters (IV, nonce...).
+pub trait Mode {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8>;
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError>;
+}
+
+fn encrypt_block<C: BlockEncrypt>(cipher: &C, bytes: &[u8]) -> Vec<u8> {
+    let mut block = Block::<C>::clone_from_slice(bytes);
+    cipher.encrypt_block(&mut block);
+    block.to_vec()
+}
+
+fn decrypt_block<C: BlockDecrypt>(cipher: &C, bytes: &[u8]) -> Vec<u8> {
+    let mut block = Block::<C>::clone_from_slice(bytes);
+    cipher.decrypt_block(&mut block);
+    block.to_vec()
+}
+
+fn check_iv<C: BlockSizeUser>(iv: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), C::block_size(), "iv must be one block long");
+    iv.to_vec()
+}
+
+fn check_length<C: BlockSizeUser>(bytes: &[u8]) -> Result<(), PaddingError> {
+    if bytes.len().is_multiple_of(C::block_size()) {
+        Ok(())
+    } else {
+        Err(PaddingError::InvalidLength)
+    }
+}
+
+pub struct Ecb<C> {
+    cipher: C,
+}
+
+impl<C> Ecb<C> {
+    pub fn new(cipher: C) -> Self {
+        Ecb { cipher }
+    }
+}
+
+impl<C: BlockEncrypt + BlockDecrypt> Mode for Ecb<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut blocks: Vec<Block<C>> = pad::pad_block(bytes, C::block_size())
+            .chunks(C::block_size())
+            .map(GenericArray::clone_from_slice)
+            .collect();
+        self.cipher.encrypt_blocks(&mut blocks);
+        blocks.iter().flatten().cloned().collect()
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        check_length::<C>(bytes)?;
+        let mut blocks: Vec<Block<C>> = bytes
+            .chunks(C::block_size())
+            .map(GenericArray::clone_from_slice)
+            .collect();
+        self.cipher.decrypt_blocks(&mut blocks);
+        let blocks: Vec<u8> = blocks.iter().flatten().cloned().collect();
+        pad::remove_padding(&blocks, C::block_size())
+    }
+}
+
+pub struct Cbc<C> {
+    cipher: C,
+    iv: Vec<u8>,
+}
+
+impl<C: BlockSizeUser> Cbc<C> {
+    pub fn new(cipher: C, iv: &[u8]) -> Self {
+        Cbc {
+            iv: check_iv::<C>(iv),
+            cipher,
+        }
+    }
+}
+
+impl<C: BlockEncrypt + BlockDecrypt> Mode for Cbc<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let bytes = pad::pad_block(bytes, C::block_size());
+        let mut prev = self.iv.clone();
+        let mut enc = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            prev = encrypt_block(&self.cipher, &xor_bytes(block, &prev));
+            enc.extend_from_slice(&prev);
+        }
+        enc
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        check_length::<C>(bytes)?;
+        let mut prev: &[u8] = &self.iv;
+        let mut dec = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            dec.append(&mut xor_bytes(&decrypt_block(&self.cipher, block), prev));
+            prev = block;
+        }
+        pad::remove_padding(&dec, C::block_size())
+    }
+}
+
+/// Propagating CBC: each block is chained with both the previous plaintext and ciphertext.
+pub struct Pcbc<C> {
+    cipher: C,
+    iv: Vec<u8>,
+}
+
+impl<C: BlockSizeUser> Pcbc<C> {
+    pub fn new(cipher: C, iv: &[u8]) -> Self {
+        Pcbc {
+            iv: check_iv::<C>(iv),
+            cipher,
+        }
+    }
+}
+
+impl<C: BlockEncrypt + BlockDecrypt> Mode for Pcbc<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let bytes = pad::pad_block(bytes, C::block_size());
+        let mut chain = self.iv.clone();
+        let mut enc = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            let ct = encrypt_block(&self.cipher, &xor_bytes(block, &chain));
+            chain = xor_bytes(block, &ct);
+            enc.extend_from_slice(&ct);
+        }
+        enc
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        check_length::<C>(bytes)?;
+        let mut chain = self.iv.clone();
+        let mut dec = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            let pt = xor_bytes(&decrypt_block(&self.cipher, block), &chain);
+            chain = xor_bytes(&pt, block);
+            dec.extend_from_slice(&pt);
+        }
+        pad::remove_padding(&dec, C::block_size())
+    }
+}
+
+/// Full-block cipher feedback. Works as a stream cipher, so there is no padding.
+pub struct Cfb<C> {
+    cipher: C,
+    iv: Vec<u8>,
+}
+
+impl<C: BlockSizeUser> Cfb<C> {
+    pub fn new(cipher: C, iv: &[u8]) -> Self {
+        Cfb {
+            iv: check_iv::<C>(iv),
+            cipher,
+        }
+    }
+}
+
+impl<C: BlockEncrypt> Mode for Cfb<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut prev = self.iv.clone();
+        let mut enc = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            let keystream = encrypt_block(&self.cipher, &prev);
+            prev = xor_bytes(block, &keystream[..block.len()]);
+            enc.extend_from_slice(&prev);
+        }
+        enc
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        let mut prev: &[u8] = &self.iv;
+        let mut dec = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            let keystream = encrypt_block(&self.cipher, prev);
+            dec.append(&mut xor_bytes(block, &keystream[..block.len()]));
+            prev = block;
+        }
+        Ok(dec)
+    }
+}
+
+/// Output feedback. Works as a stream cipher, so there is no padding.
+pub struct Ofb<C> {
+    cipher: C,
+    iv: Vec<u8>,
+}
+
+impl<C: BlockSizeUser> Ofb<C> {
+    pub fn new(cipher: C, iv: &[u8]) -> Self {
+        Ofb {
+            iv: check_iv::<C>(iv),
+            cipher,
+        }
+    }
+}
+
+impl<C: BlockEncrypt> Mode for Ofb<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut keystream = self.iv.clone();
+        let mut enc = Vec::with_capacity(bytes.len());
+        for block in bytes.chunks(C::block_size()) {
+            keystream = encrypt_block(&self.cipher, &keystream);
+            enc.append(&mut xor_bytes(block, &keystream[..block.len()]));
+        }
+        enc
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        Ok(self.encrypt(bytes))
+    }
+}
+
+/// How the nonce and block counter are laid out in a CTR counter block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterLayout {
+    /// Nonce followed by a 64-bit little-endian block counter, as in Cryptopals set 3.
+    NonceLe64,
+    /// The whole block is a big-endian counter, as in NIST SP 800-38A.
+    Be128,
+}
+
+/// CTR mode, with a byte cursor that can be moved to any offset of the keystream.
+pub struct Ctr<C> {
+    cipher: C,
+    initial: Vec<u8>,
+    layout: CounterLayout,
+    pos: u64,
+}
+
+impl<C: BlockEncrypt> Ctr<C> {
+    /// For `NonceLe64` the nonce fills the block minus the 8 counter bytes (8 bytes for AES),
+    /// so the block must be longer than 8 bytes. For `Be128` it is the full initial counter
+    /// block, which works for any block size.
+    pub fn new(cipher: C, nonce: &[u8], layout: CounterLayout) -> Self {
+        let block_size = C::block_size();
+        let mut initial = vec![0u8; block_size];
+        match layout {
+            CounterLayout::NonceLe64 => {
+                assert!(
+                    block_size > 8,
+                    "Ctr: NonceLe64 needs blocks longer than its 8-byte counter, use Be128"
+                );
+                assert_eq!(nonce.len(), block_size - 8, "Ctr: wrong nonce length");
+                initial[..block_size - 8].copy_from_slice(nonce);
+            }
+            CounterLayout::Be128 => {
+                assert_eq!(nonce.len(), block_size, "Ctr: wrong counter block length");
+                initial.copy_from_slice(nonce);
+            }
+        }
+        Ctr {
+            cipher,
+            initial,
+            layout,
+            pos: 0,
+        }
+    }
+
+    /// Current byte offset into the keystream.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Move the cursor to byte `pos` of the keystream.
+    pub fn seek(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Xor the keystream into `bytes` starting at the cursor, and advance the cursor.
+    pub fn apply_keystream(&mut self, bytes: &mut [u8]) {
+        self.apply_keystream_at(self.pos, bytes);
+        self.pos += bytes.len() as u64;
+    }
+
+    /// Xor the keystream starting at byte `offset` into `bytes`, leaving the cursor alone.
+    pub fn apply_keystream_at(&self, offset: u64, bytes: &mut [u8]) {
+        let block_size = C::block_size();
+        let mut counter = offset / block_size as u64;
+        let mut skip = (offset % block_size as u64) as usize;
+        let mut done = 0;
+        while done < bytes.len() {
+            let keystream = encrypt_block(&self.cipher, &self.counter_block(counter));
+            let n = (block_size - skip).min(bytes.len() - done);
+            xor_in_place(&mut bytes[done..done + n], &keystream[skip..skip + n]);
+            done += n;
+            skip = 0;
+            counter += 1;
+        }
+    }
+
+    /// Overwrite the plaintext under `ciphertext` from byte `offset` with `newtext`, leaving
+    /// the rest alone. The ciphertext grows if the new text runs past its end, with any gap
+    /// before `offset` filled with encrypted zeros.
+    pub fn edit(&self, ciphertext: &mut Vec<u8>, offset: u64, newtext: &[u8]) {
+        let start = offset as usize;
+        let end = start + newtext.len();
+        let len = ciphertext.len();
+        if len < start {
+            ciphertext.resize(start, 0);
+            self.apply_keystream_at(len as u64, &mut ciphertext[len..]);
+        }
+        if ciphertext.len() < end {
+            ciphertext.resize(end, 0);
+        }
+        let region = &mut ciphertext[start..end];
+        region.copy_from_slice(newtext);
+        self.apply_keystream_at(offset, region);
+    }
+
+    /// The counter block for block number `counter`.
+    fn counter_block(&self, counter: u64) -> Vec<u8> {
+        let mut block = self.initial.clone();
+        match self.layout {
+            CounterLayout::NonceLe64 => {
+                let n = block.len();
+                block[n - 8..].copy_from_slice(&counter.to_le_bytes());
+            }
+            CounterLayout::Be128 => {
+                // add counter to the block as a big-endian number, wrapping around
+                let mut carry = counter as u128;
+                for b in block.iter_mut().rev() {
+                    let sum = *b as u128 + (carry & 0xff);
+                    *b = sum as u8;
+                    carry = (carry >> 8) + (sum >> 8);
+                }
+            }
+        }
+        block
+    }
+}
+
+impl<C: BlockEncrypt> Mode for Ctr<C> {
+    fn encrypt(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        self.apply_keystream_at(0, &mut bytes);
+        bytes
+    }
+
+    fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
+        Ok(self.encrypt(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cbc, Cfb, CounterLayout, Ctr, Ecb, Mode, Ofb, Pcbc};
+    use crate::hex;
+    use aes::{
+        cipher::{consts::U8, KeyInit, KeySizeUser},
+        Aes128, Aes192, Aes256,
+    };
+
+    // NIST SP 800-38A, appendix F
+    const PT: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51";
+    const IV: &str = "000102030405060708090a0b0c0d0e0f";
+
+    fn cipher<C: KeyInit + KeySizeUser>(key: &str) -> C {
+        C::new_from_slice(&hex::parse(key).unwrap()).unwrap()
+    }
+
+    fn aes128() -> Aes128 {
+        cipher("2b7e151628aed2a6abf7158809cf4f3c")
+    }
+
+    #[test]
+    fn test_nist_vectors() {
+        let pt = hex::parse(PT).unwrap();
+        let iv = hex::parse(IV).unwrap();
+
+        let enc = Ecb::new(aes128()).encrypt(&pt);
+        assert_eq!(
+            hex::encode(&enc[..32]),
+            "3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf"
+        );
+        let enc = Cbc::new(aes128(), &iv).encrypt(&pt);
+        assert_eq!(
+            hex::encode(&enc[..32]),
+            "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2"
+        );
+        let enc = Cfb::new(aes128(), &iv).encrypt(&pt);
+        assert_eq!(
+            hex::encode(&enc),
+            "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b"
+        );
+        let enc = Ofb::new(aes128(), &iv).encrypt(&pt);
+        assert_eq!(
+            hex::encode(&enc),
+            "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825"
+        );
+
+        let key = "8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b";
+        let enc = Ecb::new(cipher::<Aes192>(key)).encrypt(&pt);
+        assert_eq!(hex::encode(&enc[..16]), "bd334f1d6e45f25ff712a214571fa5cc");
+
+        let key = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+        let enc = Ecb::new(cipher::<Aes256>(key)).encrypt(&pt);
+        assert_eq!(hex::encode(&enc[..16]), "f3eed1bdb5d2a03c064b5a7e3db181f8");
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let iv = hex::parse(IV).unwrap();
+        let modes: Vec<Box<dyn Mode>> = vec![
+            Box::new(Ecb::new(aes128())),
+            Box::new(Cbc::new(aes128(), &iv)),
+            Box::new(Pcbc::new(aes128(), &iv)),
+            Box::new(Cfb::new(aes128(), &iv)),
+            Box::new(Ofb::new(aes128(), &iv)),
+            Box::new(Ctr::new(aes128(), &iv[..8], CounterLayout::NonceLe64)),
+            Box::new(Ctr::new(aes128(), &iv, CounterLayout::Be128)),
+        ];
+        for len in [0, 1, 15, 16, 17, 40].iter() {
+            let pt: Vec<u8> = (0..*len as u8).collect();
+            for mode in modes.iter() {
+                let enc = mode.encrypt(&pt);
+                assert_eq!(mode.decrypt(&enc).unwrap(), pt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_be128_counter_carries() {
+        let iv = hex::parse("000000000000000000000000ffffffff").unwrap();
+        let ctr = Ctr::new(aes128(), &iv, CounterLayout::Be128);
+        assert_eq!(
+            hex::encode(&ctr.counter_block(1)),
+            "00000000000000000000000100000000"
+        );
+    }
+
+    /// 8-byte toy cipher: xor with the key, then rotate the block.
+    struct Toy([u8; 8]);
+
+    aes::cipher::impl_simple_block_encdec!(
+        Toy, U8, toy, block,
+        encrypt: {
+            let mut b = block.clone_in();
+            for (x, k) in b.iter_mut().zip(toy.0.iter()) {
+                *x ^= k;
+            }
+            b.rotate_left(3);
+            *block.get_out() = b;
+        }
+        decrypt: {
+            let mut b = block.clone_in();
+            b.rotate_right(3);
+            for (x, k) in b.iter_mut().zip(toy.0.iter()) {
+                *x ^= k;
+            }
+            *block.get_out() = b;
+        }
+    );
+
+    #[test]
+    fn test_small_block_cipher() {
+        let iv = *b"toy-ivs!";
+        let pt: Vec<u8> = (0..21).collect();
+        let ctr = Ctr::new(Toy(*b"toy key!"), &iv, CounterLayout::Be128);
+        assert_eq!(ctr.decrypt(&ctr.encrypt(&pt)).unwrap(), pt);
+        let cbc = Cbc::new(Toy(*b"toy key!"), &iv);
+        assert_eq!(cbc.decrypt(&cbc.encrypt(&pt)).unwrap(), pt);
+    }
+
+    #[test]
+    #[should_panic(expected = "use Be128")]
+    fn test_small_block_nonce_le64() {
+        Ctr::new(Toy(*b"toy key!"), &[], CounterLayout::NonceLe64);
+    }
+}
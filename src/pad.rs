@@ -1,32 +1,57 @@
-// pad to multiple of size
-pub fn pad_block(bytes: &[u8], mut size: usize) -> Vec<u8> {
-    let len = bytes.len();
-    while size < len {
-        size *= 2;
+use std::fmt;
+
+/// Why a PKCS#7 padded message was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingError {
+    /// The message is empty or not a multiple of the block size.
+    InvalidLength,
+    /// The last byte is zero, larger than the block size, or the padding bytes don't all match it.
+    InvalidPadding,
+}
+
+impl fmt::Display for PaddingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaddingError::InvalidLength => write!(f, "invalid padded message length"),
+            PaddingError::InvalidPadding => write!(f, "invalid PKCS#7 padding"),
+        }
     }
+}
+
+impl std::error::Error for PaddingError {}
+
+/// PKCS#7 pad to a multiple of size. A full block of padding is added if bytes already is one.
+pub fn pad_block(bytes: &[u8], size: usize) -> Vec<u8> {
+    assert!(
+        size > 0 && size < 256,
+        "pad_block: invalid block size {}",
+        size
+    );
+    let diff = size - bytes.len() % size;
     let mut bytes = bytes.to_vec();
-    let diff = size % len;
-    if diff > 0 {
-        bytes.resize(len + diff, diff as u8);
-    } else {
-        bytes.resize(len + size, size as u8);
-    }
+    bytes.resize(bytes.len() + diff, diff as u8);
     bytes
 }
 
-pub fn remove_padding(bytes: &[u8]) -> Vec<u8> {
-    if bytes.is_empty() {
-        return vec![];
+/// Strip PKCS#7 padding, checking the length and every padding byte.
+pub fn remove_padding(bytes: &[u8], size: usize) -> Result<Vec<u8>, PaddingError> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(size) {
+        return Err(PaddingError::InvalidLength);
     }
-    let mut bytes = bytes.to_vec();
     let last_byte = *bytes.last().unwrap() as usize;
-    bytes.truncate(bytes.len() - last_byte);
-    bytes
+    if last_byte == 0 || last_byte > size {
+        return Err(PaddingError::InvalidPadding);
+    }
+    let (text, padding) = bytes.split_at(bytes.len() - last_byte);
+    if padding.iter().any(|&b| b as usize != last_byte) {
+        return Err(PaddingError::InvalidPadding);
+    }
+    Ok(text.to_vec())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::pad_block;
+    use super::{pad_block, remove_padding, PaddingError};
 
     #[test]
     fn test_pad_block() {
@@ -43,4 +68,33 @@ mod tests {
             b"YELLOW SUBMARINE\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10\x10" // YELLOW SUBMARINE + 16 "16" bytes
         );
     }
+
+    #[test]
+    fn test_remove_padding() {
+        assert_eq!(
+            remove_padding(b"ICE ICE BABY\x04\x04\x04\x04", 16),
+            Ok(b"ICE ICE BABY".to_vec())
+        );
+        assert_eq!(
+            remove_padding(b"ICE ICE BABY\x05\x05\x05\x05", 16),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            remove_padding(b"ICE ICE BABY\x01\x02\x03\x04", 16),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            remove_padding(b"ICE ICE BABY\x00\x00\x00\x00", 16),
+            Err(PaddingError::InvalidPadding)
+        );
+        assert_eq!(
+            remove_padding(b"ICE ICE BABY\x04\x04\x04", 16),
+            Err(PaddingError::InvalidLength)
+        );
+        assert_eq!(remove_padding(b"", 16), Err(PaddingError::InvalidLength));
+        assert_eq!(
+            remove_padding(&pad_block(b"YELLOW SUBMARINE", 16), 16),
+            Ok(b"YELLOW SUBMARINE".to_vec())
+        );
+    }
 }
@@ -1,16 +1,90 @@
-/// xoshiro256** implementation
-/// https://prng.di.unimi.it/
+//! Pseudo random number generators.
+//! xoshiro256** implementation: https://prng.di.unimi.it/
+mod mt19937;
 
-struct SplitMix64 {
+use std::cell::Cell;
+
+pub use mt19937::{untemper, untemper64, Mt19937, Mt19937_64};
+
+/// A pseudo random number generator.
+pub trait Rng {
+    fn next_u32(&mut self) -> u32;
+    fn next_u64(&mut self) -> u64;
+
+    /// Fill a buffer with random bytes, using all eight bytes of every u64.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn get_bytes(&mut self, n: u64) -> Vec<u8> {
+        let mut bytes = vec![0u8; n as usize];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    // Random number in the range [0..range)
+    // https://www.pcg-random.org/posts/bounded-rands.html
+    fn range(&mut self, mut range: u64) -> u64 {
+        assert!(range > 0, "range: empty range");
+        let mut mask: u64 = !0;
+        range -= 1;
+        mask >>= (range | 1).leading_zeros();
+        let mut x: u64;
+        loop {
+            x = self.next_u64() & mask;
+            if x <= range {
+                break;
+            }
+        }
+        x
+    }
+
+    // Random boolean
+    fn bool(&mut self) -> bool {
+        self.range(2) == 1
+    }
+
+    /// Shuffle a slice in place (Fisher-Yates).
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.range(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Pick a random element of a slice, or None if it is empty.
+    fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.range(items.len() as u64) as usize)
+        }
+    }
+}
+
+/// A generator that can be built from a u64 seed, so runs can be reproduced.
+pub trait SeedableRng: Sized {
+    fn seed_from_u64(seed: u64) -> Self;
+
+    /// Seed from the current time.
+    fn from_entropy() -> Self {
+        Self::seed_from_u64(make_seed())
+    }
+}
+
+pub struct SplitMix64 {
     state: u64,
 }
 
 impl SplitMix64 {
-    fn new(seed: u64) -> Self {
+    pub fn new(seed: u64) -> Self {
         SplitMix64 { state: seed }
     }
 
-    fn next(&mut self) -> u64 {
+    pub fn next_num(&mut self) -> u64 {
         self.state = add(self.state, 0x9e3779b97f4a7c15_u64);
 
         let x = self.state;
@@ -37,14 +111,19 @@ fn add(a: u64, b: u64) -> u64 {
     a.overflowing_add(b).0
 }
 
-fn rotl(x: u64, k: i32) -> u64 {
-    (x << k) | (x >> (64 - k))
+fn rotl(x: u64, k: u32) -> u64 {
+    x.rotate_left(k)
 }
 
 impl Xoshiro256 {
     pub fn new(seed: u64) -> Self {
         let mut mix = SplitMix64::new(seed);
-        let state = [mix.next(), mix.next(), mix.next(), mix.next()];
+        let state = [
+            mix.next_num(),
+            mix.next_num(),
+            mix.next_num(),
+            mix.next_num(),
+        ];
         Xoshiro256 { state }
     }
 
@@ -63,43 +142,120 @@ impl Xoshiro256 {
 
         res
     }
+}
 
-    pub fn get_bytes(&mut self, n: u64) -> Vec<u8> {
-        (0..n).map(|_| self.next_num() as u8).collect()
+impl Rng for Xoshiro256 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_num() >> 32) as u32
     }
 
-    // Random number in the range [0..range)
-    // https://www.pcg-random.org/posts/bounded-rands.html
-    pub fn range(&mut self, mut range: u64) -> u64 {
-        let mut mask: u64 = !0;
-        range -= 1;
-        mask >>= (range | 1).leading_zeros();
-        let mut x: u64;
-        loop {
-            x = self.next_num() & mask;
-            if x <= range {
-                break;
-            }
+    fn next_u64(&mut self) -> u64 {
+        self.next_num()
+    }
+}
+
+impl SeedableRng for Xoshiro256 {
+    fn seed_from_u64(seed: u64) -> Self {
+        Xoshiro256::new(seed)
+    }
+}
+
+impl Rng for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_num() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next_num()
+    }
+}
+
+impl SeedableRng for SplitMix64 {
+    fn seed_from_u64(seed: u64) -> Self {
+        SplitMix64::new(seed)
+    }
+}
+
+/// Where time-based seeds come from, so code that seeds from the clock can be run against a
+/// fake one.
+pub trait Clock {
+    /// Nanoseconds since the Unix epoch.
+    fn now_nanos(&self) -> u64;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_nanos(&self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    }
+}
+
+/// A clock that reads `now`, then moves on by `step` every time it is read.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Cell<u64>,
+    step: u64,
+}
+
+impl MockClock {
+    /// A clock stopped at `now`.
+    pub fn new(now: u64) -> Self {
+        MockClock::with_step(now, 0)
+    }
+
+    pub fn with_step(now: u64, step: u64) -> Self {
+        MockClock {
+            now: Cell::new(now),
+            step,
         }
-        x
     }
 
-    // Random boolean
-    pub fn bool(&mut self) -> bool {
-        self.range(2) == 1
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.now.set(self.now.get().wrapping_add(nanos));
+    }
+}
+
+impl Clock for MockClock {
+    fn now_nanos(&self) -> u64 {
+        let now = self.now.get();
+        self.advance(self.step);
+        now
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now_nanos(&self) -> u64 {
+        (**self).now_nanos()
     }
 }
 
-fn make_seed() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64
+/// A seed from the current time in nanoseconds.
+pub fn make_seed() -> u64 {
+    make_seed_with(&SystemClock)
+}
+
+pub fn make_seed_with<C: Clock + ?Sized>(clock: &C) -> u64 {
+    clock.now_nanos()
 }
 
 pub fn make_prng() -> Xoshiro256 {
-    Xoshiro256::new(make_seed())
+    make_prng_with(&SystemClock)
+}
+
+/// A generator seeded from `clock`, as `make_prng` seeds from the system clock.
+pub fn make_prng_with<C: Clock + ?Sized>(clock: &C) -> Xoshiro256 {
+    Xoshiro256::new(make_seed_with(clock))
 }
 
 pub fn bytes(n: u64) -> Vec<u8> {
@@ -109,14 +265,17 @@ pub fn bytes(n: u64) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use super::{SplitMix64, Xoshiro256};
+    use super::{
+        make_prng_with, make_seed_with, Clock, MockClock, Rng, SeedableRng, SplitMix64,
+        SystemClock, Xoshiro256,
+    };
 
     #[test]
     fn test_splitmix64() {
         let seed = 54321u64;
         let mut mix = SplitMix64::new(seed);
         for _ in 0..5 {
-            println!("{}", mix.next());
+            println!("{}", mix.next_num());
         }
     }
 
@@ -143,4 +302,53 @@ mod tests {
         let numbers: Vec<u64> = (0..50).map(|_| prng.range(LIMIT)).collect();
         assert!(numbers.iter().all(|n| n < &LIMIT));
     }
+
+    #[test]
+    fn test_fill_bytes() {
+        let mut prng = Xoshiro256::seed_from_u64(100u64);
+        let mut bytes = [0u8; 12];
+        prng.fill_bytes(&mut bytes);
+        assert_eq!(&bytes[..8], &792317387143481937u64.to_le_bytes());
+        assert_eq!(&bytes[8..], &1418856489092323125u64.to_le_bytes()[..4]);
+    }
+
+    #[test]
+    fn test_seeded_runs_repeat() {
+        let mut a = SplitMix64::seed_from_u64(7);
+        let mut b = SplitMix64::seed_from_u64(7);
+        assert_eq!(a.get_bytes(100), b.get_bytes(100));
+
+        let mut prng = Xoshiro256::seed_from_u64(7);
+        let mut items: Vec<u32> = (0..20).collect();
+        prng.shuffle(&mut items);
+        let mut sorted = items.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..20).collect::<Vec<u32>>());
+
+        let mut again = Xoshiro256::seed_from_u64(7);
+        let mut other: Vec<u32> = (0..20).collect();
+        again.shuffle(&mut other);
+        assert_eq!(items, other);
+
+        assert!(items.contains(prng.choose(&items).unwrap()));
+        assert_eq!(prng.choose::<u32>(&[]), None);
+    }
+
+    #[test]
+    fn test_clocks() {
+        let clock = MockClock::with_step(1_000, 10);
+        assert_eq!(make_seed_with(&clock), 1_000);
+        assert_eq!(clock.now_nanos(), 1_010);
+        clock.set(42);
+        let mut a = make_prng_with(&clock);
+        assert_eq!(a.next_u64(), Xoshiro256::seed_from_u64(42).next_u64());
+
+        let stopped = MockClock::new(7);
+        stopped.advance(3);
+        assert_eq!(stopped.now_nanos(), 10);
+        assert_eq!(stopped.now_nanos(), 10);
+
+        // the real clock is well past 2020
+        assert!(SystemClock.now_nanos() > 1_577_836_800_000_000_000);
+    }
 }
@@ -0,0 +1,263 @@
+//! Mersenne Twister MT19937 and MT19937-64
+//! https://en.wikipedia.org/wiki/Mersenne_Twister
+use super::{Rng, SeedableRng};
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908b0df;
+const UPPER_MASK: u32 = 0x80000000;
+const LOWER_MASK: u32 = 0x7fffffff;
+
+const N64: usize = 312;
+const M64: usize = 156;
+const MATRIX_A64: u64 = 0xb5026f5aa96619e9;
+const UPPER_MASK64: u64 = 0xffffffff80000000;
+const LOWER_MASK64: u64 = 0x7fffffff;
+
+pub struct Mt19937 {
+    state: [u32; N],
+    index: usize,
+}
+
+impl Mt19937 {
+    pub fn new(seed: u32) -> Self {
+        let mut state = [0u32; N];
+        state[0] = seed;
+        for i in 1..N {
+            let prev = state[i - 1];
+            state[i] = 1812433253u32
+                .wrapping_mul(prev ^ (prev >> 30))
+                .wrapping_add(i as u32);
+        }
+        Mt19937 { state, index: N }
+    }
+
+    /// Rebuild a generator from 624 consecutive outputs, starting right after a twist.
+    /// The clone produces the same numbers the original does from then on.
+    pub fn clone_from_outputs(outputs: &[u32]) -> Self {
+        assert_eq!(outputs.len(), N, "clone_from_outputs: need {} outputs", N);
+        let mut state = [0u32; N];
+        for (s, &y) in state.iter_mut().zip(outputs) {
+            *s = untemper(y);
+        }
+        Mt19937 { state, index: N }
+    }
+
+    pub fn next_num(&mut self) -> u32 {
+        if self.index >= N {
+            self.twist();
+        }
+        let y = self.state[self.index];
+        self.index += 1;
+        temper(y)
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N {
+            let x = (self.state[i] & UPPER_MASK) | (self.state[(i + 1) % N] & LOWER_MASK);
+            let mut xa = x >> 1;
+            if x & 1 != 0 {
+                xa ^= MATRIX_A;
+            }
+            self.state[i] = self.state[(i + M) % N] ^ xa;
+        }
+        self.index = 0;
+    }
+}
+
+impl Rng for Mt19937 {
+    fn next_u32(&mut self) -> u32 {
+        self.next_num()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let hi = self.next_num() as u64;
+        let lo = self.next_num() as u64;
+        (hi << 32) | lo
+    }
+}
+
+impl SeedableRng for Mt19937 {
+    fn seed_from_u64(seed: u64) -> Self {
+        Mt19937::new(seed as u32)
+    }
+}
+
+pub struct Mt19937_64 {
+    state: [u64; N64],
+    index: usize,
+}
+
+impl Mt19937_64 {
+    pub fn new(seed: u64) -> Self {
+        let mut state = [0u64; N64];
+        state[0] = seed;
+        for i in 1..N64 {
+            let prev = state[i - 1];
+            state[i] = 6364136223846793005u64
+                .wrapping_mul(prev ^ (prev >> 62))
+                .wrapping_add(i as u64);
+        }
+        Mt19937_64 { state, index: N64 }
+    }
+
+    /// Rebuild a generator from 312 consecutive outputs, starting right after a twist.
+    pub fn clone_from_outputs(outputs: &[u64]) -> Self {
+        assert_eq!(
+            outputs.len(),
+            N64,
+            "clone_from_outputs: need {} outputs",
+            N64
+        );
+        let mut state = [0u64; N64];
+        for (s, &y) in state.iter_mut().zip(outputs) {
+            *s = untemper64(y);
+        }
+        Mt19937_64 { state, index: N64 }
+    }
+
+    pub fn next_num(&mut self) -> u64 {
+        if self.index >= N64 {
+            self.twist();
+        }
+        let y = self.state[self.index];
+        self.index += 1;
+        temper64(y)
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N64 {
+            let x = (self.state[i] & UPPER_MASK64) | (self.state[(i + 1) % N64] & LOWER_MASK64);
+            let mut xa = x >> 1;
+            if x & 1 != 0 {
+                xa ^= MATRIX_A64;
+            }
+            self.state[i] = self.state[(i + M64) % N64] ^ xa;
+        }
+        self.index = 0;
+    }
+}
+
+impl Rng for Mt19937_64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_num() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next_num()
+    }
+}
+
+impl SeedableRng for Mt19937_64 {
+    fn seed_from_u64(seed: u64) -> Self {
+        Mt19937_64::new(seed)
+    }
+}
+
+fn temper(mut y: u32) -> u32 {
+    y ^= y >> 11;
+    y ^= (y << 7) & 0x9d2c5680;
+    y ^= (y << 15) & 0xefc60000;
+    y ^ (y >> 18)
+}
+
+fn temper64(mut y: u64) -> u64 {
+    y ^= (y >> 29) & 0x5555555555555555;
+    y ^= (y << 17) & 0x71d67fffeda60000;
+    y ^= (y << 37) & 0xfff7eee000000000;
+    y ^ (y >> 43)
+}
+
+/// Invert the MT19937 tempering, getting back the state word an output was made from.
+pub fn untemper(y: u32) -> u32 {
+    let mut y = y as u64;
+    y = undo_right(y, 18, 0xffffffff, 32);
+    y = undo_left(y, 15, 0xefc60000, 32);
+    y = undo_left(y, 7, 0x9d2c5680, 32);
+    y = undo_right(y, 11, 0xffffffff, 32);
+    y as u32
+}
+
+/// Invert the MT19937-64 tempering.
+pub fn untemper64(y: u64) -> u64 {
+    let mut y = y;
+    y = undo_right(y, 43, !0, 64);
+    y = undo_left(y, 37, 0xfff7eee000000000, 64);
+    y = undo_left(y, 17, 0x71d67fffeda60000, 64);
+    y = undo_right(y, 29, 0x5555555555555555, 64);
+    y
+}
+
+// Invert y ^= (y >> shift) & mask. Each pass fixes another `shift` bits, from the top down.
+fn undo_right(y: u64, shift: u32, mask: u64, width: u32) -> u64 {
+    let mut x = y;
+    for _ in 0..width / shift {
+        x = y ^ ((x >> shift) & mask);
+    }
+    x
+}
+
+// Invert y ^= (y << shift) & mask, fixing `shift` more bits per pass from the bottom up.
+fn undo_left(y: u64, shift: u32, mask: u64, width: u32) -> u64 {
+    let mut x = y;
+    for _ in 0..width / shift {
+        x = y ^ ((x << shift) & mask);
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{temper, temper64, untemper, untemper64, Mt19937, Mt19937_64};
+
+    #[test]
+    fn test_mt19937() {
+        // reference values from the C++ standard library's std::mt19937
+        let mut mt = Mt19937::new(5489);
+        let numbers: Vec<u32> = (0..5).map(|_| mt.next_num()).collect();
+        assert_eq!(
+            numbers,
+            vec![3499211612, 581869302, 3890346734, 3586334585, 545404204]
+        );
+        let mut mt = Mt19937::new(5489);
+        let last = (0..10000).map(|_| mt.next_num()).last();
+        assert_eq!(last, Some(4123659995));
+    }
+
+    #[test]
+    fn test_mt19937_64() {
+        // reference value from the C++ standard library's std::mt19937_64
+        let mut mt = Mt19937_64::new(5489);
+        assert_eq!(mt.next_num(), 14514284786278117030);
+        let mut mt = Mt19937_64::new(5489);
+        let last = (0..10000).map(|_| mt.next_num()).last();
+        assert_eq!(last, Some(9981545732273789042));
+    }
+
+    #[test]
+    fn test_untemper() {
+        for &y in [0u32, 1, 0xdeadbeef, 0xffffffff, 0x12345678].iter() {
+            assert_eq!(untemper(temper(y)), y);
+        }
+        for &y in [0u64, 1, 0xdeadbeefcafebabe, !0, 0x123456789abcdef0].iter() {
+            assert_eq!(untemper64(temper64(y)), y);
+        }
+    }
+
+    #[test]
+    fn test_clone_from_outputs() {
+        let mut mt = Mt19937::new(0xc0ffee);
+        let outputs: Vec<u32> = (0..624).map(|_| mt.next_num()).collect();
+        let mut clone = Mt19937::clone_from_outputs(&outputs);
+        for _ in 0..1000 {
+            assert_eq!(clone.next_num(), mt.next_num());
+        }
+
+        let mut mt = Mt19937_64::new(0xc0ffee);
+        let outputs: Vec<u64> = (0..312).map(|_| mt.next_num()).collect();
+        let mut clone = Mt19937_64::clone_from_outputs(&outputs);
+        for _ in 0..1000 {
+            assert_eq!(clone.next_num(), mt.next_num());
+        }
+    }
+}
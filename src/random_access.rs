@@ -0,0 +1,134 @@
+//! Random-access reads and writes on CTR-encrypted data, and what goes wrong when the edit
+//! operation is exposed to someone who has the ciphertext (Cryptopals challenge 25).
+use crate::aes::{self, CounterLayout, Ctr};
+use crate::xor::xor_bytes;
+
+/// Bytes kept encrypted under AES-CTR. Any range can be read or rewritten without touching
+/// the rest, since each byte only depends on its own keystream byte.
+pub struct EncryptedBuffer {
+    ctr: Ctr,
+    data: Vec<u8>,
+}
+
+impl EncryptedBuffer {
+    /// An empty buffer. The nonce is 8 bytes, as for `aes::encrypt_ctr`.
+    pub fn new(key: &[u8], nonce: &[u8]) -> Self {
+        EncryptedBuffer {
+            ctr: Ctr::new(aes::make_cipher(key), nonce, CounterLayout::NonceLe64),
+            data: vec![],
+        }
+    }
+
+    pub fn from_plaintext(plaintext: &[u8], key: &[u8], nonce: &[u8]) -> Self {
+        let mut buffer = EncryptedBuffer::new(key, nonce);
+        buffer.write(0, plaintext);
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Decrypt up to `len` bytes from `offset`. Reads past the end come back short, like a
+    /// file's.
+    pub fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        let start = offset.min(self.data.len());
+        let end = offset.saturating_add(len).min(self.data.len());
+        let mut bytes = self.data[start..end].to_vec();
+        self.ctr.apply_keystream_at(start as u64, &mut bytes);
+        bytes
+    }
+
+    /// Write `bytes` at `offset`. Writing past the end grows the buffer, with zeros in any
+    /// gap.
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) {
+        self.ctr.edit(&mut self.data, offset as u64, bytes);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+    }
+}
+
+/// Recover the plaintext under a CTR ciphertext, given an `edit(ciphertext, offset,
+/// newtext)` oracle that re-encrypts under the same key and nonce. Writing zeros over the
+/// whole thing hands back the keystream itself.
+pub fn recover_plaintext<F>(ciphertext: &[u8], edit: F) -> Result<Vec<u8>, String>
+where
+    F: Fn(&[u8], usize, &[u8]) -> Vec<u8>,
+{
+    let keystream = edit(ciphertext, 0, &vec![0u8; ciphertext.len()]);
+    if keystream.len() != ciphertext.len() {
+        return Err(format!(
+            "random_access: edit returned {} bytes, expected {}",
+            keystream.len(),
+            ciphertext.len()
+        ));
+    }
+    Ok(xor_bytes(ciphertext, &keystream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recover_plaintext, EncryptedBuffer};
+    use crate::aes;
+    use crate::rand;
+
+    const KEY: &[u8] = b"YELLOW SUBMARINE";
+    const NONCE: [u8; 8] = [9u8; 8];
+    const TEXT: &[u8] =
+        b"I'm back and I'm ringin' the bell\nA rockin' on the mike while the fly girls yell";
+
+    #[test]
+    fn test_buffer() {
+        let mut buffer = EncryptedBuffer::from_plaintext(TEXT, KEY, &NONCE);
+        assert_eq!(buffer.len(), TEXT.len());
+        assert_eq!(
+            buffer.ciphertext(),
+            &aes::encrypt_ctr(TEXT, KEY, &NONCE)[..]
+        );
+        assert_eq!(buffer.read(0, TEXT.len()), TEXT);
+        assert_eq!(buffer.read(17, 7), b"ringin'");
+        assert_eq!(buffer.read(TEXT.len() - 4, 100), b"yell");
+        assert!(buffer.read(TEXT.len() + 10, 5).is_empty());
+
+        let before = buffer.ciphertext().to_vec();
+        buffer.write(17, b"RINGIN'");
+        assert_eq!(&buffer.ciphertext()[..17], &before[..17]);
+        assert_eq!(&buffer.ciphertext()[24..], &before[24..]);
+        assert_eq!(buffer.read(13, 15), b"I'm RINGIN' the");
+
+        buffer.truncate(4);
+        buffer.write(6, b"!");
+        assert_eq!(buffer.read(0, 10), b"I'm \0\0!");
+        assert_eq!(
+            aes::decrypt_ctr(buffer.ciphertext(), KEY, &NONCE),
+            b"I'm \0\0!"
+        );
+
+        let empty = EncryptedBuffer::new(KEY, &NONCE);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_recover_plaintext() {
+        let key = rand::bytes(aes::KEY_SIZE as u64);
+        let nonce = rand::bytes(8);
+        let ct = aes::encrypt_ctr(TEXT, &key, &nonce);
+        let edit = |ct: &[u8], offset: usize, newtext: &[u8]| {
+            aes::edit_ctr(ct, &key, &nonce, offset, newtext)
+        };
+        assert_eq!(recover_plaintext(&ct, edit).unwrap(), TEXT);
+
+        let truncating = |ct: &[u8], _: usize, _: &[u8]| ct[1..].to_vec();
+        assert!(recover_plaintext(&ct, truncating).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Breaking repeating-key xor (Cryptopals challenge 6): guess the key size, solve each
+//! column as single-byte xor, and rank the resulting plaintexts.
+use std::ops::RangeInclusive;
+
+use crate::score::Scorer;
+use crate::{hamming, rank_single_byte_xor, xor};
+
+/// Key sizes tried by default.
+pub const KEY_SIZES: RangeInclusive<usize> = 2..=40;
+
+/// At most this many chunks are compared when measuring a key size's Hamming distance, so
+/// the cost doesn't grow with the square of the input.
+const MAX_CHUNKS: usize = 64;
+
+/// How likely a key size is, from the ciphertext alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySize {
+    pub size: usize,
+    /// Hamming distance in bits per byte, averaged over pairs of key-size chunks.
+    pub distance: f64,
+    /// Index of coincidence of the columns, relative to uniformly random bytes.
+    pub ioc: f64,
+}
+
+impl KeySize {
+    /// Higher is more likely. Text xored with the right key keeps the low distance and high
+    /// coincidence of the plaintext; the wrong size makes both look random.
+    pub fn score(&self) -> f64 {
+        self.ioc / self.distance.max(f64::EPSILON)
+    }
+}
+
+/// A candidate key and what it decrypts to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub key: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    pub score: f64,
+}
+
+/// Rate every key size in `sizes`, best first. Multiples of the real size score about as
+/// well as it does. Sizes that don't fit twice in the input are left out, so a short input
+/// gives an empty list.
+pub fn rank_key_sizes(bytes: &[u8], sizes: RangeInclusive<usize>) -> Vec<KeySize> {
+    let mut ranked: Vec<KeySize> = sizes
+        .filter(|&size| size > 0 && bytes.len() / size >= 2)
+        .map(|size| KeySize {
+            size,
+            distance: chunk_distance(bytes, size),
+            ioc: columns(bytes, size)
+                .iter()
+                .map(|c| index_of_coincidence(c))
+                .sum::<f64>()
+                / size as f64,
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score().total_cmp(&a.score()).then(a.size.cmp(&b.size)));
+    ranked
+}
+
+/// Normalized Hamming distance over all pairs of up to `MAX_CHUNKS` full `size`-byte chunks,
+/// spread evenly over the input.
+fn chunk_distance(bytes: &[u8], size: usize) -> f64 {
+    let count = bytes.len() / size;
+    let sampled = count.min(MAX_CHUNKS);
+    let chunk = |i: usize| {
+        let start = i * count / sampled * size;
+        &bytes[start..start + size]
+    };
+    let mut total = 0u64;
+    let mut pairs = 0u64;
+    for i in 0..sampled {
+        for j in i + 1..sampled {
+            total += hamming(chunk(i), chunk(j)) as u64;
+            pairs += 1;
+        }
+    }
+    total as f64 / (pairs * size as u64) as f64
+}
+
+/// Probability that two bytes picked from `bytes` are equal, times 256.
+fn index_of_coincidence(bytes: &[u8]) -> f64 {
+    let n = bytes.len() as f64;
+    if n < 2.0 {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+    let same: u64 = counts.iter().map(|c| c * c.saturating_sub(1)).sum();
+    256.0 * same as f64 / (n * (n - 1.0))
+}
+
+/// Transpose `bytes` into the `size` columns encrypted under each key byte.
+fn columns(bytes: &[u8], size: usize) -> Vec<Vec<u8>> {
+    (0..size)
+        .map(|i| bytes.iter().skip(i).step_by(size).cloned().collect())
+        .collect()
+}
+
+/// The most likely key of a given size, solving each column as single-byte xor.
+pub fn find_key<S: Scorer + ?Sized>(bytes: &[u8], size: usize, scorer: &S) -> Vec<u8> {
+    columns(bytes, size)
+        .iter()
+        .map(|column| rank_single_byte_xor(column, scorer, 1)[0].key)
+        .collect()
+}
+
+/// Try the `top` most likely key sizes from `KEY_SIZES` and return the solutions, best
+/// first. Keys that just repeat a shorter key are reduced to it, so a multiple of the real
+/// key size does not show up as a separate answer.
+pub fn break_repeating_xor<S: Scorer + ?Sized>(
+    bytes: &[u8],
+    scorer: &S,
+    top: usize,
+) -> Vec<Solution> {
+    let mut solutions: Vec<Solution> = vec![];
+    for keysize in rank_key_sizes(bytes, KEY_SIZES).iter().take(top) {
+        let key = find_key(bytes, keysize.size, scorer);
+        let key = key[..period(&key)].to_vec();
+        if solutions.iter().any(|s| s.key == key) {
+            continue;
+        }
+        let plaintext: Vec<u8> = xor::xor_repeating(bytes, &key).collect();
+        solutions.push(Solution {
+            score: scorer.score(&plaintext),
+            key,
+            plaintext,
+        });
+    }
+    solutions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    solutions
+}
+
+/// Length of the shortest prefix that `key` is a repetition of.
+fn period(key: &[u8]) -> usize {
+    (1..key.len())
+        .filter(|p| key.len().is_multiple_of(*p))
+        .find(|&p| key.chunks(p).all(|c| c == &key[..p]))
+        .unwrap_or(key.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{break_repeating_xor, period, rank_key_sizes, KEY_SIZES};
+    use crate::score::NgramModel;
+    use crate::xor::xor_repeating;
+
+    const TEXT: &[u8] = b"Call me Ishmael. Some years ago, never mind how long precisely, having \
+        little or no money in my purse, and nothing particular to interest me on shore, I thought \
+        I would sail about a little and see the watery part of the world. It is a way I have of \
+        driving off the spleen and regulating the circulation. Whenever I find myself growing \
+        grim about the mouth; whenever it is a damp, drizzly November in my soul; whenever I find \
+        myself involuntarily pausing before coffin warehouses, and bringing up the rear of every \
+        funeral I meet; then, I account it high time to get to sea as soon as I can.";
+
+    #[test]
+    fn test_break_repeating_xor() {
+        let key = b"Terminator X";
+        let ct: Vec<u8> = xor_repeating(TEXT, key).collect();
+
+        // multiples of the key size look just as good, so any of them may come first
+        let best = rank_key_sizes(&ct, KEY_SIZES)[0].size;
+        assert!(best.is_multiple_of(key.len()));
+        let solutions = break_repeating_xor(&ct, &NgramModel::english_unigrams(), 3);
+        assert_eq!(solutions[0].key, key);
+        assert_eq!(solutions[0].plaintext, TEXT);
+        assert!(solutions.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn test_long_input() {
+        // only a sample of the chunks is compared, so this stays quick
+        let text = TEXT.repeat(400);
+        let key = b"Vanilla Ice";
+        let ct: Vec<u8> = xor_repeating(&text, key).collect();
+        let best = rank_key_sizes(&ct, KEY_SIZES)[0].size;
+        assert!(best.is_multiple_of(key.len()));
+    }
+
+    #[test]
+    fn test_short_input() {
+        assert!(rank_key_sizes(b"abc", KEY_SIZES).is_empty());
+        assert!(break_repeating_xor(b"", &NgramModel::english_unigrams(), 3).is_empty());
+        assert_eq!(rank_key_sizes(b"abcd", KEY_SIZES).len(), 1);
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(period(b"abcabc"), 3);
+        assert_eq!(period(b"aaaa"), 1);
+        assert_eq!(period(b"abcab"), 5);
+    }
+}
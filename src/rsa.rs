@@ -0,0 +1,320 @@
+//! RSA: key generation, raw and PKCS#1 v1.5 encryption, and PKCS#1 v1.5 SHA-1 signatures with
+//! an optional sloppy verifier (Cryptopals challenges 39-42).
+use crate::bigint::BigUint;
+use crate::rand::Rng;
+use crate::sha1;
+
+/// DER encoding of the SHA-1 DigestInfo header that precedes the hash in a signature.
+const SHA1_DIGEST_INFO: [u8; 15] = [
+    0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14,
+];
+
+const MILLER_RABIN_ROUNDS: usize = 40;
+
+/// How strictly a signature's padding is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verifier {
+    /// Rebuild the whole encoded block and compare.
+    Strict,
+    /// Parse 00 01 FF.. 00 DigestInfo HASH from the left and ignore whatever follows the hash,
+    /// like the broken implementations Bleichenbacher's e=3 forgery targets.
+    Sloppy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    pub n: BigUint,
+    pub e: BigUint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateKey {
+    pub n: BigUint,
+    d: BigUint,
+}
+
+pub struct Keypair {
+    pub public: PublicKey,
+    pub private: PrivateKey,
+}
+
+impl Keypair {
+    /// Generate a key with a modulus of exactly `bits` bits and public exponent e.
+    pub fn generate<R: Rng>(bits: usize, e: u64, rng: &mut R) -> Self {
+        assert!(bits >= 32 && bits.is_multiple_of(2), "rsa: bad key size");
+        let e = BigUint::from(e);
+        loop {
+            let p = generate_prime(bits / 2, rng);
+            let q = generate_prime(bits / 2, rng);
+            if p == q {
+                continue;
+            }
+            let phi = &(&p - 1) * &(&q - 1);
+            if let Some(d) = e.modinv(&phi) {
+                let n = &p * &q;
+                return Keypair {
+                    public: PublicKey { n: n.clone(), e },
+                    private: PrivateKey { n, d },
+                };
+            }
+        }
+    }
+}
+
+/// Length of the modulus in bytes.
+fn modulus_len(n: &BigUint) -> usize {
+    n.bits().div_ceil(8)
+}
+
+impl PublicKey {
+    pub fn size(&self) -> usize {
+        modulus_len(&self.n)
+    }
+
+    /// Textbook RSA: m^e mod n.
+    pub fn encrypt(&self, m: &BigUint) -> BigUint {
+        m.modpow(&self.e, &self.n)
+    }
+
+    /// Encrypt with PKCS#1 v1.5 type 2 padding.
+    pub fn encrypt_pkcs1<R: Rng>(&self, msg: &[u8], rng: &mut R) -> Result<Vec<u8>, String> {
+        let em = pkcs1_pad_encrypt(msg, self.size(), rng)?;
+        let c = self.encrypt(&BigUint::from_bytes_be(&em));
+        Ok(c.to_bytes_be_padded(self.size()))
+    }
+
+    /// Check a PKCS#1 v1.5 SHA-1 signature over a message. The signature must be exactly as
+    /// long as the modulus and, as an integer, less than it; otherwise s + n would verify too.
+    pub fn verify(&self, msg: &[u8], signature: &[u8], verifier: Verifier) -> bool {
+        if signature.len() != self.size() {
+            return false;
+        }
+        let s = BigUint::from_bytes_be(signature);
+        if s >= self.n {
+            return false;
+        }
+        let em = self.encrypt(&s).to_bytes_be_padded(self.size());
+        let hash = sha1::digest(msg);
+        match verifier {
+            Verifier::Strict => pkcs1_encode_signature(&hash, self.size()).is_ok_and(|e| e == em),
+            Verifier::Sloppy => sloppy_signature_hash(&em).is_some_and(|h| h == hash),
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Textbook RSA: c^d mod n.
+    pub fn decrypt(&self, c: &BigUint) -> BigUint {
+        c.modpow(&self.d, &self.n)
+    }
+
+    pub fn decrypt_pkcs1(&self, ct: &[u8]) -> Result<Vec<u8>, String> {
+        let k = modulus_len(&self.n);
+        let em = self.decrypt(&BigUint::from_bytes_be(ct));
+        pkcs1_unpad_encrypt(&em.to_bytes_be_padded(k))
+    }
+
+    /// PKCS#1 v1.5 signature over the SHA-1 hash of a message.
+    pub fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, String> {
+        let k = modulus_len(&self.n);
+        let em = pkcs1_encode_signature(&sha1::digest(msg), k)?;
+        let s = self.decrypt(&BigUint::from_bytes_be(&em));
+        Ok(s.to_bytes_be_padded(k))
+    }
+}
+
+/// 00 02 || nonzero random bytes || 00 || msg, `k` bytes long.
+pub fn pkcs1_pad_encrypt<R: Rng>(msg: &[u8], k: usize, rng: &mut R) -> Result<Vec<u8>, String> {
+    if msg.len() + 11 > k {
+        return Err("rsa: message too long".to_string());
+    }
+    let mut em = vec![0x00, 0x02];
+    while em.len() < k - msg.len() - 1 {
+        let b = rng.next_u32() as u8;
+        if b != 0 {
+            em.push(b);
+        }
+    }
+    em.push(0x00);
+    em.extend_from_slice(msg);
+    Ok(em)
+}
+
+pub fn pkcs1_unpad_encrypt(em: &[u8]) -> Result<Vec<u8>, String> {
+    if em.len() < 11 || em[0] != 0x00 || em[1] != 0x02 {
+        return Err("rsa: invalid PKCS#1 encryption block".to_string());
+    }
+    match em[2..].iter().position(|&b| b == 0) {
+        Some(i) if i >= 8 => Ok(em[2 + i + 1..].to_vec()),
+        _ => Err("rsa: invalid PKCS#1 encryption block".to_string()),
+    }
+}
+
+/// 00 01 || FF.. || 00 || DigestInfo || hash, `k` bytes long.
+pub fn pkcs1_encode_signature(hash: &[u8], k: usize) -> Result<Vec<u8>, String> {
+    let t_len = SHA1_DIGEST_INFO.len() + hash.len();
+    if t_len + 11 > k {
+        return Err("rsa: modulus too short".to_string());
+    }
+    let mut em = vec![0x00, 0x01];
+    em.resize(k - t_len - 1, 0xff);
+    em.push(0x00);
+    em.extend_from_slice(&SHA1_DIGEST_INFO);
+    em.extend_from_slice(hash);
+    Ok(em)
+}
+
+fn sloppy_signature_hash(em: &[u8]) -> Option<&[u8]> {
+    if em.len() < 3 || em[0] != 0x00 || em[1] != 0x01 || em[2] != 0xff {
+        return None;
+    }
+    let rest = &em[2..];
+    let ffs = rest.iter().position(|&b| b != 0xff)?;
+    let rest = &rest[ffs..];
+    if rest.first() != Some(&0x00) || !rest[1..].starts_with(&SHA1_DIGEST_INFO) {
+        return None;
+    }
+    rest.get(1 + SHA1_DIGEST_INFO.len()..1 + SHA1_DIGEST_INFO.len() + sha1::OUTPUT_SIZE)
+}
+
+/// Random prime of exactly `bits` bits, with the top two bits set so that the product of
+/// two of them has exactly twice as many.
+pub fn generate_prime<R: Rng>(bits: usize, rng: &mut R) -> BigUint {
+    assert!(bits >= 16, "generate_prime: too few bits");
+    loop {
+        let mut candidate = BigUint::random_bits(rng, bits);
+        candidate.set_bit(bits - 1);
+        candidate.set_bit(bits - 2);
+        candidate.set_bit(0);
+        if is_probable_prime(&candidate, MILLER_RABIN_ROUNDS, rng) {
+            return candidate;
+        }
+    }
+}
+
+/// Trial division by small primes, then Miller-Rabin with `rounds` random bases.
+pub fn is_probable_prime<R: Rng>(n: &BigUint, rounds: usize, rng: &mut R) -> bool {
+    for p in small_primes() {
+        let p = BigUint::from(p);
+        if *n == p {
+            return true;
+        }
+        if (n % &p).is_zero() {
+            return false;
+        }
+    }
+    if *n < BigUint::from(2u64) {
+        return false;
+    }
+
+    // n - 1 = d * 2^s
+    let n_minus_1 = n - 1;
+    let s = n_minus_1.trailing_zeros();
+    let d = &n_minus_1 >> s;
+    let three = BigUint::from(3u64);
+
+    'witness: for _ in 0..rounds {
+        // a in [2, n - 2]
+        let a = &BigUint::random_below(rng, &(n - &three)) + 2;
+        let mut x = a.modpow(&d, n);
+        if x.is_one() || x == n_minus_1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = &(&x * &x) % n;
+            if x == n_minus_1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Primes below 1000, by the sieve of Eratosthenes.
+fn small_primes() -> Vec<u64> {
+    const LIMIT: usize = 1000;
+    let mut sieve = vec![true; LIMIT];
+    sieve[0] = false;
+    sieve[1] = false;
+    for i in 2..LIMIT {
+        if sieve[i] {
+            for j in (i * i..LIMIT).step_by(i) {
+                sieve[j] = false;
+            }
+        }
+    }
+    (0..LIMIT as u64).filter(|&i| sieve[i as usize]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_probable_prime, Keypair, Verifier, SHA1_DIGEST_INFO};
+    use crate::bigint::BigUint;
+    use crate::rand::{SeedableRng, Xoshiro256};
+    use crate::sha1;
+
+    #[test]
+    fn test_primality() {
+        let mut rng = Xoshiro256::seed_from_u64(39);
+        let primes = [2u64, 3, 997, 1009, 104729, 2147483647];
+        for &p in primes.iter() {
+            assert!(is_probable_prime(&BigUint::from(p), 20, &mut rng));
+        }
+        // 561 is a Carmichael number
+        let composites = [0u64, 1, 561, 1009 * 1013, 2147483647 * 2147483647];
+        for &c in composites.iter() {
+            assert!(!is_probable_prime(&BigUint::from(c), 20, &mut rng));
+        }
+        let m127 = (BigUint::one() << 127) - 1;
+        assert!(is_probable_prime(&m127, 20, &mut rng));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let mut rng = Xoshiro256::seed_from_u64(40);
+        let key = Keypair::generate(512, 65537, &mut rng);
+        assert_eq!(key.public.n.bits(), 512);
+
+        let m = BigUint::from_bytes_be(b"YELLOW SUBMARINE");
+        let c = key.public.encrypt(&m);
+        assert_eq!(key.private.decrypt(&c), m);
+
+        let ct = key.public.encrypt_pkcs1(b"hi mom", &mut rng).unwrap();
+        assert_eq!(ct.len(), 64);
+        assert_eq!(key.private.decrypt_pkcs1(&ct).unwrap(), b"hi mom");
+        assert!(key.public.encrypt_pkcs1(&[0u8; 54], &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_signatures() {
+        let mut rng = Xoshiro256::seed_from_u64(42);
+        let key = Keypair::generate(1024, 3, &mut rng);
+        let sig = key.private.sign(b"hi mom").unwrap();
+        assert!(key.public.verify(b"hi mom", &sig, Verifier::Strict));
+        assert!(key.public.verify(b"hi mom", &sig, Verifier::Sloppy));
+        assert!(!key.public.verify(b"hi dad", &sig, Verifier::Strict));
+
+        // Bleichenbacher's e=3 forgery: put the hash right after a single FF, zero the rest
+        // and round the cube root up. Cubing it only disturbs the zeroed tail.
+        let mut block = vec![0x00, 0x01, 0xff, 0x00];
+        block.extend_from_slice(&SHA1_DIGEST_INFO);
+        block.extend_from_slice(&sha1::digest(b"hi mom"));
+        block.resize(key.public.size(), 0x00);
+        let forged = &BigUint::from_bytes_be(&block).cbrt() + 1;
+        let forged = forged.to_bytes_be_padded(key.public.size());
+
+        assert!(key.public.verify(b"hi mom", &forged, Verifier::Sloppy));
+        assert!(!key.public.verify(b"hi mom", &forged, Verifier::Strict));
+
+        // s + n has the same e-th power mod n, but isn't a valid encoding of a signature
+        let s = BigUint::from_bytes_be(&sig);
+        let k = key.public.size();
+        let malleated = (&s + &key.public.n).to_bytes_be_padded(k + 1);
+        assert!(!key.public.verify(b"hi mom", &malleated, Verifier::Strict));
+        assert!(!key.public.verify(b"hi mom", &malleated, Verifier::Sloppy));
+        // the right integer, but not k bytes long
+        let padded = s.to_bytes_be_padded(k + 1);
+        assert!(!key.public.verify(b"hi mom", &padded, Verifier::Strict));
+    }
+}
@@ -0,0 +1,315 @@
+//! Scoring candidate plaintexts by how much they look like English.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Relative frequencies of characters in English prose, letters folded to lowercase.
+const ENGLISH_UNIGRAMS: [(u8, f64); 47] = [
+    (b'a', 0.08167),
+    (b'b', 0.01492),
+    (b'c', 0.02782),
+    (b'd', 0.04253),
+    (b'e', 0.12702),
+    (b'f', 0.02228),
+    (b'g', 0.02015),
+    (b'h', 0.06094),
+    (b'i', 0.06966),
+    (b'j', 0.00153),
+    (b'k', 0.00772),
+    (b'l', 0.04025),
+    (b'm', 0.02406),
+    (b'n', 0.06749),
+    (b'o', 0.07507),
+    (b'p', 0.01929),
+    (b'q', 0.00095),
+    (b'r', 0.05987),
+    (b's', 0.06327),
+    (b't', 0.09056),
+    (b'u', 0.02758),
+    (b'v', 0.00978),
+    (b'w', 0.02360),
+    (b'x', 0.00150),
+    (b'y', 0.01974),
+    (b'z', 0.00074),
+    (b' ', 0.19000),
+    (b'.', 0.00650),
+    (b',', 0.00610),
+    (b'\'', 0.00240),
+    (b'"', 0.00260),
+    (b'-', 0.00150),
+    (b'\n', 0.00500),
+    (b'!', 0.00050),
+    (b'?', 0.00050),
+    (b';', 0.00030),
+    (b':', 0.00030),
+    (b'0', 0.00100),
+    (b'1', 0.00100),
+    (b'2', 0.00080),
+    (b'3', 0.00060),
+    (b'4', 0.00060),
+    (b'5', 0.00060),
+    (b'6', 0.00050),
+    (b'7', 0.00050),
+    (b'8', 0.00050),
+    (b'9', 0.00060),
+];
+
+/// Subtracted from a score for every unit fraction of non-printable bytes.
+const NONPRINTABLE_PENALTY: f64 = 20.0;
+
+/// Something that rates how likely a byte string is to be plaintext. Higher is better.
+/// Scores are normalized by length so candidates of different lengths can be compared.
+pub trait Scorer {
+    fn score(&self, bytes: &[u8]) -> f64;
+}
+
+impl<S: Scorer + ?Sized> Scorer for &S {
+    fn score(&self, bytes: &[u8]) -> f64 {
+        (**self).score(bytes)
+    }
+}
+
+impl<S: Scorer + ?Sized> Scorer for Box<S> {
+    fn score(&self, bytes: &[u8]) -> f64 {
+        (**self).score(bytes)
+    }
+}
+
+/// Printable ASCII plus the usual whitespace.
+fn is_printable(b: u8) -> bool {
+    (0x20..0x7f).contains(&b) || b == b'\n' || b == b'\r' || b == b'\t'
+}
+
+/// Penalty for the fraction of bytes that could not appear in text.
+fn nonprintable_penalty(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let bad = bytes.iter().filter(|&&b| !is_printable(b)).count();
+    NONPRINTABLE_PENALTY * bad as f64 / bytes.len() as f64
+}
+
+fn read_corpus(path: impl AsRef<Path>) -> Result<Vec<u8>, String> {
+    let path = path.as_ref();
+    fs::read(path).map_err(|e| format!("score: reading {}: {}", path.display(), e))
+}
+
+/// Pearson's chi-squared statistic of the byte histogram against expected frequencies,
+/// negated so that closer is higher. Bytes the model has never seen fall into one shared
+/// bucket.
+pub struct ChiSquared {
+    expected: [f64; 256],
+    other: f64,
+}
+
+impl ChiSquared {
+    /// Build from relative frequencies, which need not sum to one.
+    pub fn new(frequencies: &[(u8, f64)]) -> Self {
+        let total: f64 = frequencies.iter().map(|(_, f)| f).sum();
+        let mut expected = [0.0; 256];
+        for &(b, f) in frequencies {
+            expected[b.to_ascii_lowercase() as usize] += f / total;
+        }
+        // a little room for anything else, so a stray digit is not infinitely unlikely
+        ChiSquared {
+            expected,
+            other: 0.001,
+        }
+    }
+
+    pub fn english() -> Self {
+        ChiSquared::new(&ENGLISH_UNIGRAMS)
+    }
+
+    pub fn from_corpus(corpus: &[u8]) -> Self {
+        let mut counts = HashMap::new();
+        for &b in corpus {
+            *counts.entry(b.to_ascii_lowercase()).or_insert(0.0) += 1.0;
+        }
+        ChiSquared::new(&counts.into_iter().collect::<Vec<_>>())
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        Ok(ChiSquared::from_corpus(&read_corpus(path)?))
+    }
+}
+
+impl Scorer for ChiSquared {
+    fn score(&self, bytes: &[u8]) -> f64 {
+        if bytes.is_empty() {
+            return 0.0;
+        }
+        let mut observed = [0usize; 256];
+        for &b in bytes {
+            observed[b.to_ascii_lowercase() as usize] += 1;
+        }
+
+        let len = bytes.len() as f64;
+        let mut chi2 = 0.0;
+        let mut other = 0usize;
+        for (o, p) in observed.iter().zip(self.expected.iter()) {
+            if *p > 0.0 {
+                let e = p * len;
+                chi2 += (*o as f64 - e).powi(2) / e;
+            } else {
+                other += o;
+            }
+        }
+        let e = self.other * len;
+        chi2 += (other as f64 - e).powi(2) / e;
+
+        -chi2 / len - nonprintable_penalty(bytes)
+    }
+}
+
+/// An n-gram language model: the score is the average log-probability of every n-gram in
+/// the text, with letters folded to lowercase. Unigrams give a plain log-likelihood score;
+/// bigrams and trigrams also reward plausible letter order.
+pub struct NgramModel {
+    n: usize,
+    log_probs: HashMap<Vec<u8>, f64>,
+    // log-probability of an n-gram that never showed up in the corpus
+    floor: f64,
+}
+
+impl NgramModel {
+    /// Build from n-gram counts, which need not be normalized.
+    pub fn from_counts(n: usize, counts: HashMap<Vec<u8>, f64>) -> Self {
+        assert!(n > 0, "NgramModel: n must be positive");
+        let total: f64 = counts.values().sum();
+        let log_probs = counts
+            .into_iter()
+            .filter(|(gram, _)| gram.len() == n)
+            .map(|(gram, c)| (gram, (c / total).ln()))
+            .collect();
+        NgramModel {
+            n,
+            log_probs,
+            floor: (0.01 / total.max(1.0)).ln(),
+        }
+    }
+
+    /// Count the n-grams of a sample text.
+    pub fn from_corpus(corpus: &[u8], n: usize) -> Self {
+        assert!(n > 0, "NgramModel: n must be positive");
+        let corpus: Vec<u8> = corpus.iter().map(|b| b.to_ascii_lowercase()).collect();
+        let mut counts = HashMap::new();
+        for gram in corpus.windows(n) {
+            *counts.entry(gram.to_vec()).or_insert(0.0) += 1.0;
+        }
+        NgramModel::from_counts(n, counts)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, n: usize) -> Result<Self, String> {
+        if n == 0 {
+            return Err("score: n must be positive".to_string());
+        }
+        Ok(NgramModel::from_corpus(&read_corpus(path)?, n))
+    }
+
+    /// Unigram model from a built-in English frequency table.
+    pub fn english_unigrams() -> Self {
+        let counts = ENGLISH_UNIGRAMS
+            .iter()
+            .map(|&(b, f)| (vec![b], f))
+            .collect();
+        let mut model = NgramModel::from_counts(1, counts);
+        model.floor = (0.0001f64).ln();
+        model
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    pub fn log_prob(&self, gram: &[u8]) -> f64 {
+        *self.log_probs.get(gram).unwrap_or(&self.floor)
+    }
+}
+
+impl Scorer for NgramModel {
+    fn score(&self, bytes: &[u8]) -> f64 {
+        if bytes.len() < self.n {
+            return self.floor - nonprintable_penalty(bytes);
+        }
+        let lower: Vec<u8> = bytes.iter().map(|b| b.to_ascii_lowercase()).collect();
+        let windows = lower.windows(self.n);
+        let count = windows.len() as f64;
+        windows.map(|gram| self.log_prob(gram)).sum::<f64>() / count - nonprintable_penalty(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChiSquared, NgramModel, Scorer};
+    use std::env;
+    use std::fs;
+
+    const CORPUS: &[u8] = b"It was the best of times, it was the worst of times, it was the age \
+        of wisdom, it was the age of foolishness, it was the epoch of belief, it was the epoch of \
+        incredulity, it was the season of Light, it was the season of Darkness, it was the spring \
+        of hope, it was the winter of despair.";
+
+    #[test]
+    fn test_scorers_prefer_english() {
+        let scorers: Vec<Box<dyn Scorer>> = vec![
+            Box::new(ChiSquared::english()),
+            Box::new(ChiSquared::from_corpus(CORPUS)),
+            Box::new(NgramModel::english_unigrams()),
+            Box::new(NgramModel::from_corpus(CORPUS, 2)),
+            Box::new(NgramModel::from_corpus(CORPUS, 3)),
+        ];
+        let english = b"Cooking MC's like a pound of bacon";
+        let garbage = b"\x1b77316?x\x15\x1b\x7f+x413=x9x(7-6<x7>x:9;76";
+        let shouty = b"XPPSKTIXEPPIREH'WIJTPLQSIVFEGS";
+        for scorer in scorers.iter() {
+            assert!(scorer.score(english) > scorer.score(garbage));
+            assert!(scorer.score(english) > scorer.score(shouty));
+        }
+    }
+
+    #[test]
+    fn test_english_digits() {
+        // no digit should fall through to the floor for unseen characters
+        let model = NgramModel::english_unigrams();
+        for d in b'0'..=b'9' {
+            assert!(model.log_prob(&[d]) > model.log_prob(b"~"), "{}", d as char);
+        }
+    }
+
+    #[test]
+    fn test_ngram_order() {
+        // same letters, scrambled order: only models with context can tell
+        let bigrams = NgramModel::from_corpus(CORPUS, 2);
+        let unigrams = NgramModel::from_corpus(CORPUS, 1);
+        assert_eq!(bigrams.n(), 2);
+        let (text, scrambled) = (b"the season of hope", b"eht nossae fo epho");
+        assert!(bigrams.score(text) > bigrams.score(scrambled));
+        assert!((unigrams.score(text) - unigrams.score(scrambled)).abs() < 1e-9);
+        assert!(bigrams.log_prob(b"th") > bigrams.log_prob(b"qz"));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = env::temp_dir().join("cryptopals-score-corpus.txt");
+        fs::write(&path, CORPUS).unwrap();
+        let from_file = NgramModel::from_file(&path, 3).unwrap();
+        let chi = ChiSquared::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let text = b"it was the age of wisdom";
+        assert_eq!(
+            from_file.score(text),
+            NgramModel::from_corpus(CORPUS, 3).score(text)
+        );
+        assert_eq!(chi.score(text), ChiSquared::from_corpus(CORPUS).score(text));
+        assert!(NgramModel::from_file(env::temp_dir().join("no-such-corpus"), 1).is_err());
+        assert!(NgramModel::from_file(&path, 0).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "n must be positive")]
+    fn test_zero_gram() {
+        NgramModel::from_corpus(CORPUS, 0);
+    }
+}
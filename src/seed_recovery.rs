@@ -0,0 +1,133 @@
+//! Recovering the seed of a generator seeded from the clock (Cryptopals challenges 22 and 24).
+//! If we know roughly when it was seeded, only the seeds in that window need to be tried.
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use crate::rand::{Rng, SeedableRng};
+
+/// Nanoseconds in a second, for building windows out of `Clock` readings.
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Seeds from `radius` before `center` to `radius` after it.
+pub fn window_around(center: u64, radius: u64) -> Range<u64> {
+    center.saturating_sub(radius)..center.saturating_add(radius).saturating_add(1)
+}
+
+/// How many threads to search with by default.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// The lowest seed in `window` for which `matches` holds, trying them on `threads` threads.
+/// Each thread takes a contiguous slice of the window and gives up once a lower match has
+/// been found elsewhere.
+pub fn search<F>(window: Range<u64>, threads: usize, matches: F) -> Option<u64>
+where
+    F: Fn(u64) -> bool + Sync,
+{
+    if window.is_empty() {
+        return None;
+    }
+    let threads = threads.max(1) as u64;
+    let per_thread = (window.end - window.start).div_ceil(threads);
+    // window.end means nothing found yet
+    let best = AtomicU64::new(window.end);
+
+    thread::scope(|scope| {
+        for t in 0..threads {
+            let start = window.start.saturating_add(t * per_thread);
+            let end = start.saturating_add(per_thread).min(window.end);
+            let (best, matches) = (&best, &matches);
+            scope.spawn(move || {
+                for seed in start..end {
+                    if seed >= best.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if matches(seed) {
+                        best.fetch_min(seed, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    Some(best.into_inner()).filter(|&seed| seed < window.end)
+}
+
+/// Find the seed in `window` that makes an `R` start with `output`. With fewer than 8 bytes
+/// of output, several seeds may match; the lowest is returned.
+pub fn recover_seed<R: Rng + SeedableRng>(output: &[u8], window: Range<u64>) -> Option<u64> {
+    search(window, default_threads(), |seed| {
+        R::seed_from_u64(seed).get_bytes(output.len() as u64) == output
+    })
+}
+
+/// Was `token` the first output of an `R` seeded from the clock within `tolerance`
+/// nanoseconds of `issued_at`? Returns the seed if so.
+pub fn detect_time_seeded<R: Rng + SeedableRng>(
+    token: &[u8],
+    issued_at: u64,
+    tolerance: u64,
+) -> Option<u64> {
+    recover_seed::<R>(token, window_around(issued_at, tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_time_seeded, recover_seed, search, window_around, NANOS_PER_SEC};
+    use crate::rand::{self, MockClock, Mt19937, Rng, SeedableRng, Xoshiro256};
+
+    const NOW: u64 = 1_700_000_000 * NANOS_PER_SEC;
+
+    #[test]
+    fn test_search() {
+        for &threads in [1, 3, 8].iter() {
+            assert_eq!(search(0..1000, threads, |s| s % 97 == 50), Some(50));
+            assert_eq!(search(10..20, threads, |s| s == 19), Some(19));
+            assert_eq!(search(0..1000, threads, |_| false), None);
+        }
+        assert_eq!(search(5..5, 4, |_| true), None);
+        assert_eq!(window_around(3, 5), 0..9);
+    }
+
+    #[test]
+    fn test_recover_clock_seed() {
+        // a token generator that seeds from the clock every time it is called
+        let clock = MockClock::new(NOW);
+        clock.advance(12_345);
+        let token = rand::make_prng_with(&clock).get_bytes(16);
+
+        let seed = detect_time_seeded::<rand::Xoshiro256>(&token, NOW, 50_000).unwrap();
+        assert_eq!(seed, NOW + 12_345);
+        // with the seed, every later token from the same generator is known too
+        let mut ours = Xoshiro256::seed_from_u64(seed);
+        let mut theirs = rand::make_prng_with(&MockClock::new(seed));
+        assert_eq!(ours.get_bytes(48), theirs.get_bytes(48));
+
+        // issued outside the window we looked at
+        assert_eq!(
+            detect_time_seeded::<rand::Xoshiro256>(&token, NOW + 100_000, 50_000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_not_time_seeded() {
+        let token = Xoshiro256::seed_from_u64(0xdead_beef_cafe).get_bytes(16);
+        assert_eq!(
+            detect_time_seeded::<rand::Xoshiro256>(&token, NOW, 20_000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mt19937_unix_seconds() {
+        // challenge 22: MT19937 seeded with the time in seconds, some minutes ago
+        let seed = 1_700_000_000 + 40 + 917;
+        let output = Mt19937::seed_from_u64(seed).get_bytes(8);
+        let window = window_around(1_700_000_000 + 1000, 1000);
+        assert_eq!(recover_seed::<Mt19937>(&output, window), Some(seed));
+    }
+}
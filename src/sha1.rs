@@ -0,0 +1,186 @@
+//! SHA-1 (FIPS 180-4), with its internal state exposed so it can be resumed for
+//! length-extension attacks.
+use crate::hash::{BlockBuffer, Hash};
+
+pub use crate::hash::md_padding;
+
+pub const BLOCK_SIZE: usize = 64;
+pub const OUTPUT_SIZE: usize = 20;
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+#[derive(Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: BlockBuffer,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Sha1::from_state(INITIAL_STATE, 0)
+    }
+
+    /// Resume hashing from a digest state after `len` bytes (message plus padding) were processed.
+    pub fn from_state(state: [u32; 5], len: u64) -> Self {
+        Sha1 {
+            state,
+            buffer: BlockBuffer::new(BLOCK_SIZE, len),
+        }
+    }
+
+    pub fn state(&self) -> [u32; 5] {
+        self.state
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.update(bytes, |block| compress(state, block));
+    }
+
+    pub fn finalize(mut self) -> [u8; OUTPUT_SIZE] {
+        let padding = md_padding(self.buffer.len());
+        self.update(&padding);
+        debug_assert!(self.buffer.is_aligned());
+
+        let mut digest = [0u8; OUTPUT_SIZE];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// Run the compression function over one block.
+fn compress(state: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0u32; 80];
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, wi) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*wi);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+
+    for (s, x) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+        *s = s.wrapping_add(*x);
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hash for Sha1 {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    const OUTPUT_SIZE: usize = OUTPUT_SIZE;
+
+    fn new() -> Self {
+        Sha1::new()
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        Sha1::update(self, bytes)
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        Sha1::finalize(self).to_vec()
+    }
+}
+
+/// Hash a message in one go.
+pub fn digest(bytes: &[u8]) -> [u8; OUTPUT_SIZE] {
+    let mut sha = Sha1::new();
+    sha.update(bytes);
+    sha.finalize()
+}
+
+/// Split a digest back into the five state words it was made from.
+pub fn state_from_digest(digest: &[u8]) -> [u32; 5] {
+    assert_eq!(digest.len(), OUTPUT_SIZE);
+    let mut state = [0u32; 5];
+    for (word, chunk) in state.iter_mut().zip(digest.chunks(4)) {
+        *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest, md_padding, state_from_digest, Sha1};
+    use crate::hex;
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(
+            hex::encode(&digest(b"")),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+        assert_eq!(
+            hex::encode(&digest(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex::encode(&digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+
+        // a million a's, fed in uneven pieces
+        let mut sha = Sha1::new();
+        for chunk in vec![b'a'; 1_000_000].chunks(999) {
+            sha.update(chunk);
+        }
+        assert_eq!(
+            hex::encode(&sha.finalize()),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    #[test]
+    fn test_length_extension() {
+        let key = b"YELLOW SUBMARINE";
+        let msg = b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+        let extension = b";admin=true";
+
+        let secret: Vec<u8> = key.iter().chain(msg.iter()).cloned().collect();
+        let mac = digest(&secret);
+
+        // the attacker only knows the mac and the length of key + message
+        let glue = md_padding(secret.len() as u64);
+        let processed = (secret.len() + glue.len()) as u64;
+        let mut forger = Sha1::from_state(state_from_digest(&mac), processed);
+        forger.update(extension);
+        let forged = forger.finalize();
+
+        let extended: Vec<u8> = secret
+            .iter()
+            .chain(glue.iter())
+            .chain(extension.iter())
+            .cloned()
+            .collect();
+        assert_eq!(forged, digest(&extended));
+    }
+}
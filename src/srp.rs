@@ -0,0 +1,420 @@
+//! Secure Remote Password (SRP-6a) with an in-process server, the "simplified" variant that
+//! drops the k * v term, and an offline dictionary attack on the latter (Cryptopals
+//! challenges 36-38).
+use std::collections::HashMap;
+
+use crate::bigint::BigUint;
+use crate::dh::Group;
+use crate::hex;
+use crate::hmac::{self, Comparison};
+use crate::rand::Rng;
+use crate::sha256::{self, Sha256};
+
+const SALT_SIZE: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// B = k * v + g^b with k = H(N || PAD(g)), u = H(PAD(A) || PAD(B)).
+    Srp6a,
+    /// B = g^b and the server picks a random u, so the client's proof only depends on the
+    /// password through values the server chose.
+    Simplified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// The client introduces itself with its public value A.
+    Hello { email: String, public: BigUint },
+    /// Hex-encoded HMAC-SHA256(K, salt).
+    Proof { mac: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The user's salt and the server's public value B. Only the simplified variant sends u.
+    Challenge {
+        salt: Vec<u8>,
+        public: BigUint,
+        u: Option<BigUint>,
+    },
+    Accepted,
+    Denied,
+}
+
+/// Anything a client can log in to.
+pub trait Handler {
+    fn handle<R: Rng>(&mut self, request: Request, rng: &mut R) -> Result<Response, String>;
+}
+
+/// What the server stores per user instead of the password.
+struct Record {
+    salt: Vec<u8>,
+    verifier: BigUint,
+}
+
+/// A login in progress.
+struct Session {
+    salt: Vec<u8>,
+    key: Vec<u8>,
+}
+
+pub struct Server {
+    group: Group,
+    variant: Variant,
+    users: HashMap<String, Record>,
+    session: Option<Session>,
+}
+
+impl Server {
+    pub fn new(group: Group, variant: Variant) -> Self {
+        Server {
+            group,
+            variant,
+            users: HashMap::new(),
+            session: None,
+        }
+    }
+
+    /// Store a salt and the verifier v = g^x for a user.
+    pub fn register<R: Rng>(&mut self, email: &str, password: &str, rng: &mut R) {
+        let salt = rng.get_bytes(SALT_SIZE);
+        let verifier = self
+            .group
+            .g
+            .modpow(&password_hash(&salt, password), &self.group.p);
+        self.users
+            .insert(email.to_string(), Record { salt, verifier });
+    }
+
+    fn hello<R: Rng>(&mut self, email: &str, a: BigUint, rng: &mut R) -> Result<Response, String> {
+        let Group { p, g } = &self.group;
+        // A = 0 (mod N) would force the shared secret to zero whatever the password
+        if (&a % p).is_zero() {
+            return Err("srp: invalid public value".to_string());
+        }
+        let record = self
+            .users
+            .get(email)
+            .ok_or_else(|| format!("srp: unknown user {}", email))?;
+
+        let b = BigUint::random_below(rng, p);
+        let (public, u) = match self.variant {
+            Variant::Srp6a => {
+                let kv = &record.verifier * &multiplier(&self.group);
+                let public = &(&kv + &g.modpow(&b, p)) % p;
+                let u = hash_publics(&self.group, &a, &public);
+                (public, u)
+            }
+            Variant::Simplified => (g.modpow(&b, p), BigUint::random_bits(rng, 128)),
+        };
+        if u.is_zero() {
+            return Err("srp: u is zero".to_string());
+        }
+
+        // S = (A * v^u)^b
+        let base = &(&a * &record.verifier.modpow(&u, p)) % p;
+        let key = hash_secret(&base.modpow(&b, p));
+        self.session = Some(Session {
+            salt: record.salt.clone(),
+            key,
+        });
+
+        Ok(Response::Challenge {
+            salt: record.salt.clone(),
+            public,
+            u: match self.variant {
+                Variant::Srp6a => None,
+                Variant::Simplified => Some(u),
+            },
+        })
+    }
+
+    fn proof(&mut self, mac: &str) -> Result<Response, String> {
+        let session = self
+            .session
+            .take()
+            .ok_or_else(|| "srp: proof without a handshake".to_string())?;
+        let mac = hex::parse(mac)?;
+        if hmac::verify::<Sha256>(&session.key, &session.salt, &mac, Comparison::ConstantTime) {
+            Ok(Response::Accepted)
+        } else {
+            Ok(Response::Denied)
+        }
+    }
+}
+
+impl Handler for Server {
+    fn handle<R: Rng>(&mut self, request: Request, rng: &mut R) -> Result<Response, String> {
+        match request {
+            Request::Hello { email, public } => self.hello(&email, public, rng),
+            Request::Proof { mac } => self.proof(&mac),
+        }
+    }
+}
+
+pub struct Client {
+    group: Group,
+    variant: Variant,
+    email: String,
+    password: String,
+    private: BigUint,
+    public: BigUint,
+}
+
+impl Client {
+    pub fn new<R: Rng>(
+        group: Group,
+        variant: Variant,
+        email: &str,
+        password: &str,
+        rng: &mut R,
+    ) -> Self {
+        let private = BigUint::random_below(rng, &group.p);
+        let public = group.g.modpow(&private, &group.p);
+        Client {
+            group,
+            variant,
+            email: email.to_string(),
+            password: password.to_string(),
+            private,
+            public,
+        }
+    }
+
+    pub fn hello(&self) -> Request {
+        Request::Hello {
+            email: self.email.clone(),
+            public: self.public.clone(),
+        }
+    }
+
+    /// Answer the server's challenge with a proof of the shared key.
+    pub fn prove(&self, challenge: &Response) -> Result<Request, String> {
+        let (salt, b, u) = match challenge {
+            Response::Challenge { salt, public, u } => (salt, public, u),
+            _ => return Err(format!("srp: expected a challenge, got {:?}", challenge)),
+        };
+        let Group { p, g } = &self.group;
+        // B = 0 (mod N) or u = 0 would let the server fix the key without knowing v
+        if (b % p).is_zero() {
+            return Err("srp: invalid public value".to_string());
+        }
+        let x = password_hash(salt, &self.password);
+
+        let (base, u) = match (self.variant, u) {
+            // S = (B - k * g^x)^(a + u * x)
+            (Variant::Srp6a, None) => {
+                let kgx = &(&g.modpow(&x, p) * &multiplier(&self.group)) % p;
+                let base = &(&(b % p) + p) - &kgx;
+                (&base % p, hash_publics(&self.group, &self.public, b))
+            }
+            // S = B^(a + u * x)
+            (Variant::Simplified, Some(u)) => (b % p, u.clone()),
+            _ => return Err("srp: challenge does not match the protocol variant".to_string()),
+        };
+        if u.is_zero() {
+            return Err("srp: u is zero".to_string());
+        }
+        let exp = &self.private + &(&u * &x);
+        let key = hash_secret(&base.modpow(&exp, p));
+        Ok(Request::Proof {
+            mac: hex::encode(&hmac::hmac::<Sha256>(&key, salt)),
+        })
+    }
+}
+
+/// Run a whole login against a server, returning whether it let the client in.
+pub fn log_in<H: Handler, R: Rng>(
+    client: &Client,
+    server: &mut H,
+    rng: &mut R,
+) -> Result<bool, String> {
+    let challenge = server.handle(client.hello(), rng)?;
+    let proof = client.prove(&challenge)?;
+    Ok(server.handle(proof, rng)? == Response::Accepted)
+}
+
+/// A man in the middle posing as a simplified-SRP server. It picks b = 1 and u = 1 so that
+/// the client's proof can be checked against password guesses offline: S = A * g^x.
+pub struct Mallory {
+    group: Group,
+    salt: Vec<u8>,
+    client_public: Option<BigUint>,
+    mac: Option<Vec<u8>>,
+}
+
+impl Mallory {
+    pub fn new(group: Group) -> Self {
+        Mallory {
+            group,
+            salt: vec![],
+            client_public: None,
+            mac: None,
+        }
+    }
+
+    /// Try every password in a dictionary against the captured proof.
+    pub fn crack<'a>(&self, dictionary: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        let (a, mac) = (self.client_public.as_ref()?, self.mac.as_ref()?);
+        let Group { p, g } = &self.group;
+        dictionary.into_iter().find(|password| {
+            let v = g.modpow(&password_hash(&self.salt, password), p);
+            let key = hash_secret(&(&(a * &v) % p));
+            hmac::verify::<Sha256>(&key, &self.salt, mac, Comparison::ConstantTime)
+        })
+    }
+}
+
+impl Handler for Mallory {
+    fn handle<R: Rng>(&mut self, request: Request, rng: &mut R) -> Result<Response, String> {
+        match request {
+            Request::Hello { public, .. } => {
+                self.client_public = Some(public);
+                self.salt = rng.get_bytes(SALT_SIZE);
+                Ok(Response::Challenge {
+                    salt: self.salt.clone(),
+                    public: self.group.g.clone(),
+                    u: Some(BigUint::one()),
+                })
+            }
+            Request::Proof { mac } => {
+                self.mac = Some(hex::parse(mac)?);
+                Ok(Response::Accepted)
+            }
+        }
+    }
+}
+
+/// x = H(salt || password)
+fn password_hash(salt: &[u8], password: &str) -> BigUint {
+    let mut sha = Sha256::new();
+    sha.update(salt);
+    sha.update(password.as_bytes());
+    BigUint::from_bytes_be(&sha.finalize())
+}
+
+/// Length of N in bytes, which PAD() pads to.
+fn pad_len(group: &Group) -> usize {
+    group.p.bits().div_ceil(8)
+}
+
+/// k = H(N || PAD(g))
+fn multiplier(group: &Group) -> BigUint {
+    let mut sha = Sha256::new();
+    sha.update(&group.p.to_bytes_be());
+    sha.update(&group.g.to_bytes_be_padded(pad_len(group)));
+    BigUint::from_bytes_be(&sha.finalize())
+}
+
+/// u = H(PAD(A) || PAD(B))
+fn hash_publics(group: &Group, a: &BigUint, b: &BigUint) -> BigUint {
+    let mut sha = Sha256::new();
+    sha.update(&a.to_bytes_be_padded(pad_len(group)));
+    sha.update(&b.to_bytes_be_padded(pad_len(group)));
+    BigUint::from_bytes_be(&sha.finalize())
+}
+
+/// K = H(S)
+fn hash_secret(s: &BigUint) -> Vec<u8> {
+    sha256::digest(&s.to_bytes_be()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log_in, multiplier, Client, Handler, Mallory, Request, Response, Server, Variant};
+    use crate::bigint::BigUint;
+    use crate::dh::Group;
+    use crate::rand::{SeedableRng, Xoshiro256};
+
+    const EMAIL: &str = "alice@example.com";
+
+    #[test]
+    fn test_log_in() {
+        let mut rng = Xoshiro256::seed_from_u64(36);
+        for &variant in [Variant::Srp6a, Variant::Simplified].iter() {
+            let mut server = Server::new(Group::nist(), variant);
+            server.register(EMAIL, "hunter2", &mut rng);
+
+            let client = Client::new(Group::nist(), variant, EMAIL, "hunter2", &mut rng);
+            assert_eq!(log_in(&client, &mut server, &mut rng), Ok(true));
+            let client = Client::new(Group::nist(), variant, EMAIL, "hunter3", &mut rng);
+            assert_eq!(log_in(&client, &mut server, &mut rng), Ok(false));
+        }
+
+        let mut server = Server::new(Group::nist(), Variant::Srp6a);
+        let client = Client::new(Group::nist(), Variant::Srp6a, EMAIL, "hunter2", &mut rng);
+        assert!(log_in(&client, &mut server, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_zero_key() {
+        // challenge 37: A = 0, N or 2N would make S = 0 without knowing the password
+        let mut rng = Xoshiro256::seed_from_u64(37);
+        let group = Group::nist();
+        let mut server = Server::new(group.clone(), Variant::Srp6a);
+        server.register(EMAIL, "hunter2", &mut rng);
+        for i in 0..3u64 {
+            let request = Request::Hello {
+                email: EMAIL.to_string(),
+                public: &group.p * i,
+            };
+            assert!(server.handle(request, &mut rng).is_err());
+        }
+    }
+
+    #[test]
+    fn test_multiplier() {
+        // not SRP-6's k = 3, and it depends on the group
+        let k = multiplier(&Group::nist());
+        assert_ne!(k, BigUint::from(3u64));
+        assert_ne!(k, multiplier(&Group::rfc3526_2048()));
+    }
+
+    #[test]
+    fn test_client_checks() {
+        let mut rng = Xoshiro256::seed_from_u64(39);
+        let group = Group::nist();
+        let challenge = |public: BigUint, u: Option<BigUint>| Response::Challenge {
+            salt: vec![0u8; 16],
+            public,
+            u,
+        };
+
+        let client = Client::new(group.clone(), Variant::Srp6a, EMAIL, "hunter2", &mut rng);
+        for i in 0..3u64 {
+            assert!(client.prove(&challenge(&group.p * i, None)).is_err());
+        }
+        assert!(client.prove(&challenge(group.g.clone(), None)).is_ok());
+
+        let client = Client::new(
+            group.clone(),
+            Variant::Simplified,
+            EMAIL,
+            "hunter2",
+            &mut rng,
+        );
+        let zero = Some(BigUint::zero());
+        assert!(client.prove(&challenge(group.g.clone(), zero)).is_err());
+        let one = Some(BigUint::one());
+        assert!(client.prove(&challenge(group.g.clone(), one)).is_ok());
+    }
+
+    #[test]
+    fn test_dictionary_attack() {
+        let mut rng = Xoshiro256::seed_from_u64(38);
+        let dictionary = ["password", "123456", "letmein", "hunter2", "trustno1"];
+        let client = Client::new(
+            Group::nist(),
+            Variant::Simplified,
+            EMAIL,
+            "hunter2",
+            &mut rng,
+        );
+
+        let mut mallory = Mallory::new(Group::nist());
+        assert_eq!(mallory.crack(dictionary.iter().cloned()), None);
+        assert_eq!(log_in(&client, &mut mallory, &mut rng), Ok(true));
+        assert_eq!(mallory.crack(dictionary.iter().cloned()), Some("hunter2"));
+        assert_eq!(mallory.crack(["hunter3"].iter().cloned()), None);
+    }
+}
@@ -1,5 +1,166 @@
+//! Xor over byte slices. Everything here runs in time that depends only on the lengths of its
+//! inputs, and only `xor_bytes` allocates.
+use std::convert::TryInto;
+use std::iter::FusedIterator;
+use std::slice;
+
+const WORD: usize = 8;
+
+/// Xor `src` into `dst`. Both must be equally long.
+pub fn xor_in_place(dst: &mut [u8], src: &[u8]) {
+    assert_eq!(dst.len(), src.len(), "xor: length mismatch");
+    let mut dst_words = dst.chunks_exact_mut(WORD);
+    let mut src_words = src.chunks_exact(WORD);
+    for (d, s) in (&mut dst_words).zip(&mut src_words) {
+        let x = u64::from_ne_bytes(d[..].try_into().unwrap())
+            ^ u64::from_ne_bytes(s.try_into().unwrap());
+        d.copy_from_slice(&x.to_ne_bytes());
+    }
+    for (d, s) in dst_words
+        .into_remainder()
+        .iter_mut()
+        .zip(src_words.remainder())
+    {
+        *d ^= s;
+    }
+}
+
+/// Write `a ^ b` into `dst`. All three must be equally long.
+pub fn xor_into(dst: &mut [u8], a: &[u8], b: &[u8]) {
+    assert_eq!(a.len(), b.len(), "xor: length mismatch");
+    dst.copy_from_slice(a);
+    xor_in_place(dst, b);
+}
+
 /// Xor two equally long byte slices.
 pub fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
-    assert_eq!(a.len(), b.len()); // TODO: ???
-    a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
-}
\ No newline at end of file
+    let mut out = a.to_vec();
+    xor_in_place(&mut out, b);
+    out
+}
+
+// keys shorter than this are first repeated into a longer one on the stack
+const SHORT_KEY: usize = 64;
+
+/// Xor a key repeated over the whole of `dst` into it.
+pub fn xor_repeating_in_place(dst: &mut [u8], key: &[u8]) {
+    assert!(!key.is_empty(), "xor: empty key");
+    if key.len() < SHORT_KEY {
+        // a whole number of keys that is also a whole number of words, and at least
+        // SHORT_KEY long; lcm(len, WORD) <= 63 * 8, so this always fits
+        let mut period = key.len() * WORD / gcd(key.len(), WORD);
+        period *= SHORT_KEY.div_ceil(period);
+        let mut repeated = [0u8; SHORT_KEY * WORD];
+        for chunk in repeated[..period].chunks_mut(key.len()) {
+            chunk.copy_from_slice(key);
+        }
+        return xor_repeating_in_place(dst, &repeated[..period]);
+    }
+    for chunk in dst.chunks_mut(key.len()) {
+        let n = chunk.len();
+        xor_in_place(chunk, &key[..n]);
+    }
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Lazily xor `bytes` with `key` repeated to the same length.
+pub fn xor_repeating<'a>(bytes: &'a [u8], key: &'a [u8]) -> RepeatingKeyXor<'a> {
+    assert!(!key.is_empty(), "xor: empty key");
+    RepeatingKeyXor {
+        bytes: bytes.iter(),
+        key,
+        pos: 0,
+    }
+}
+
+/// Iterator returned by `xor_repeating`.
+#[derive(Clone)]
+pub struct RepeatingKeyXor<'a> {
+    bytes: slice::Iter<'a, u8>,
+    key: &'a [u8],
+    pos: usize,
+}
+
+impl Iterator for RepeatingKeyXor<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.bytes.next()?;
+        let k = self.key[self.pos];
+        self.pos = (self.pos + 1) % self.key.len();
+        Some(b ^ k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.bytes.size_hint()
+    }
+}
+
+impl ExactSizeIterator for RepeatingKeyXor<'_> {}
+
+impl FusedIterator for RepeatingKeyXor<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{xor_bytes, xor_in_place, xor_into, xor_repeating, xor_repeating_in_place};
+
+    #[test]
+    fn test_xor() {
+        // lengths either side of the word size, so both the word loop and the tail run
+        for len in [0, 1, 7, 8, 9, 23, 64].iter() {
+            let a: Vec<u8> = (0..*len as u8).collect();
+            let b: Vec<u8> = (0..*len as u8).map(|x| x.wrapping_mul(37)).collect();
+            let expected: Vec<u8> = a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect();
+
+            assert_eq!(xor_bytes(&a, &b), expected);
+            let mut dst = a.clone();
+            xor_in_place(&mut dst, &b);
+            assert_eq!(dst, expected);
+            let mut dst = vec![0xff; *len];
+            xor_into(&mut dst, &a, &b);
+            assert_eq!(dst, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_xor_length_mismatch() {
+        xor_bytes(b"abc", b"ab");
+    }
+
+    #[test]
+    fn test_xor_repeating() {
+        let bytes = b"Burning 'em, if you ain't quick and nimble";
+        let expected: Vec<u8> = bytes
+            .iter()
+            .zip(b"ICE".iter().cycle())
+            .map(|(x, y)| x ^ y)
+            .collect();
+
+        let iter = xor_repeating(bytes, b"ICE");
+        assert_eq!(iter.len(), bytes.len());
+        assert_eq!(iter.collect::<Vec<u8>>(), expected);
+
+        let mut dst = bytes.to_vec();
+        xor_repeating_in_place(&mut dst, b"ICE");
+        assert_eq!(dst, expected);
+
+        // keys on both sides of the short-key cutoff, over lengths that aren't a multiple
+        let bytes: Vec<u8> = (0..1000u32).map(|i| (i * 7 + 3) as u8).collect();
+        for &key_len in [1, 5, 8, 13, 63, 64, 65, 200].iter() {
+            let key: Vec<u8> = (0..key_len).map(|i| (i * 31 + 1) as u8).collect();
+            let expected: Vec<u8> = xor_repeating(&bytes, &key).collect();
+            let mut dst = bytes.clone();
+            xor_repeating_in_place(&mut dst, &key);
+            assert_eq!(dst, expected, "key length {}", key_len);
+        }
+    }
+}